@@ -0,0 +1,63 @@
+use k::exec::debug::{Step, Stop};
+use k::exec::i10::Interpreter;
+use std::io::{self, Write};
+use reader::Reader;
+
+fn frame(i: &Interpreter, n: usize) -> String {
+    match i.backtrace().get(n).and_then(|f| f.name) {
+        Some(x) => i.name(x),
+        None => "lambda".to_string(),
+    }
+}
+
+pub fn backtrace(i: &Interpreter) {
+    for (n, f) in i.backtrace().iter().enumerate().rev() {
+        println!("[{}] {} {}", n, frame(i, n), i.format(&f.lambda));
+    }
+}
+
+// Nested REPL run while the interpreter is suspended, input is evaluated in
+// the innermost frame.
+//   \s   step into
+//   \n   step over
+//   \r   resume
+//   \bt  backtrace
+pub fn hook(i: &mut Interpreter, stop: Stop) -> Step {
+    let depth = i.backtrace().len();
+    let name = if depth > 0 { frame(i, depth - 1) } else { String::new() };
+    match stop {
+        Stop::Call => println!("break {}", name),
+        Stop::Statement(s) => println!("{}", i.format(&s)),
+        Stop::Error(e) => println!("{}", ::error(e)),
+    }
+    let mut input = Reader::new(io::stdin());
+    loop {
+        print!("{}))", name);
+        io::stdout().flush().unwrap();
+        let line = match input.next(|| ()) {
+            Some(l) => l,
+            None => return Step::Run,
+        };
+        match line.trim() {
+            "\\s" => return Step::Into,
+            "\\n" => return Step::Over(depth),
+            "\\r" => return Step::Run,
+            "\\bt" => backtrace(i),
+            t if t.starts_with('\\') || depth == 0 => {
+                match ::exec(i, t) {
+                    Ok(u) => i.print(&u),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            t => {
+                let r = i.parse(t.as_bytes()).map_err(::error).and_then(|n| {
+                    i.run_at(&n, depth - 1).map_err(::error)
+                });
+                match r {
+                    Ok(u) => i.print(&u),
+                    Err(e) => println!("{}", e),
+                }
+            }
+        }
+    }
+}
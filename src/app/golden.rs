@@ -0,0 +1,155 @@
+use k::parse::ast::AST;
+use k::exec::i10;
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process;
+use reader;
+
+// A golden file is a K script where an expression may be followed by the lines
+// it is expected to print, each prefixed with "/ ":
+//
+//     1+2
+//     / 3
+//     zz
+//     / 'undefined
+//
+// Expressions without expected lines are run but not checked. Each file runs
+// in an empty directory of its own that is removed afterwards, so files it
+// writes are named relative to it.
+struct Case {
+    line: usize,
+    expr: String,
+    expected: Vec<String>,
+}
+
+fn cases(text: &str) -> Vec<Case> {
+    let mut r: Vec<Case> = Vec::new();
+    let mut expr = String::new();
+    let mut start = 0;
+    for (n, l) in text.lines().enumerate() {
+        if expr.is_empty() {
+            if l.starts_with('/') {
+                match r.last_mut() {
+                    Some(c) if c.line + c.expr.lines().count() + c.expected.len() == n + 1 => {
+                        let e = if l.starts_with("/ ") { &l[2..] } else { &l[1..] };
+                        c.expected.push(e.to_string());
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+            if l.trim().is_empty() {
+                continue;
+            }
+            start = n + 1;
+        } else {
+            expr.push('\n');
+        }
+        expr.push_str(l);
+        if reader::balanced(&expr) {
+            r.push(Case {
+                line: start,
+                expr: expr,
+                expected: vec![],
+            });
+            expr = String::new();
+        }
+    }
+    r
+}
+
+// Runs one file in a fresh interpreter, returns (passed, failed).
+fn file(path: &Path) -> (usize, usize) {
+    let mut text = String::new();
+    if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut text)) {
+        println!("{}: {}", path.display(), e);
+        return (0, 1);
+    }
+    let cwd = env::current_dir();
+    let dir = env::temp_dir().join(format!("k-test-{}-{}",
+                                           process::id(),
+                                           path.file_stem().and_then(|x| x.to_str()).unwrap_or("")));
+    let _ = fs::remove_dir_all(&dir);
+    if let Err(e) = fs::create_dir_all(&dir).and_then(|_| env::set_current_dir(&dir)) {
+        println!("{}: {}", dir.display(), e);
+        return (0, 1);
+    }
+    let r = check(path, &text);
+    if let Ok(d) = cwd {
+        let _ = env::set_current_dir(d);
+    }
+    let _ = fs::remove_dir_all(&dir);
+    r
+}
+
+fn check(path: &Path, text: &str) -> (usize, usize) {
+    let mut i = i10::new();
+    i.set_console(0, 0);
+    let (mut passed, mut failed) = (0, 0);
+    for c in cases(text) {
+        let got = match ::exec(&mut i, &c.expr) {
+            Ok(AST::Quit) => break,
+            Ok(u) => i.format(&u),
+            Err(e) => e,
+        };
+        if c.expected.is_empty() {
+            continue;
+        }
+        let lines: Vec<&str> = got.lines().collect();
+        if lines == c.expected.iter().map(|x| &x[..]).collect::<Vec<&str>>() {
+            passed += 1;
+        } else {
+            failed += 1;
+            println!("{}:{}: {}", path.display(), c.line, c.expr);
+            println!("expected:");
+            for l in c.expected.iter() {
+                println!("  {}", l);
+            }
+            println!("got:");
+            for l in lines {
+                println!("  {}", l);
+            }
+        }
+    }
+    (passed, failed)
+}
+
+// Runs a golden file or every .k file of a directory, prints a summary.
+pub fn run(path: &str) -> bool {
+    let p = Path::new(path);
+    let mut files: Vec<PathBuf> = Vec::new();
+    if p.is_dir() {
+        if let Ok(d) = fs::read_dir(p) {
+            for e in d.filter_map(|e| e.ok()) {
+                if e.path().extension().map_or(false, |x| x == "k") {
+                    files.push(e.path());
+                }
+            }
+        }
+        files.sort();
+    } else {
+        files.push(p.to_path_buf());
+    }
+    let (mut passed, mut failed) = (0, 0);
+    for f in files.iter() {
+        let (ok, ko) = file(f);
+        passed += ok;
+        failed += ko;
+    }
+    println!("{} files, {} passed, {} failed", files.len(), passed, failed);
+    failed == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The behaviour tests of the language, failures are printed with what
+    // was expected.
+    #[test]
+    fn golden_files() {
+        assert!(run(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/k")));
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+// Request heads beyond this are refused.
+const MAX_HEAD: usize = 16384;
+
+pub struct Request {
+    // such as /?1+2
+    pub target: String,
+    // user:password of basic authorization, empty without
+    pub login: String,
+}
+
+// A GET request, other headers are read and ignored. None for anything else.
+pub fn request(s: &TcpStream) -> Option<Request> {
+    let mut r = BufReader::new(s);
+    let mut line = String::new();
+    let mut size = 0;
+    let mut target: Option<String> = None;
+    let mut login = String::new();
+    loop {
+        line.clear();
+        match r.read_line(&mut line) {
+            Ok(0) | Err(_) => return None,
+            Ok(n) => size += n,
+        }
+        if size > MAX_HEAD {
+            return None;
+        }
+        if target.is_none() {
+            let v: Vec<&str> = line.split_whitespace().collect();
+            match &v[..] {
+                &["GET", t, _] => target = Some(t.to_string()),
+                _ => return None,
+            }
+        } else if line.trim().is_empty() {
+            return target.map(|t| {
+                Request {
+                    target: t,
+                    login: login,
+                }
+            });
+        } else if let Some(b) = basic(&line) {
+            login = b;
+        }
+    }
+}
+
+// Credentials of an Authorization: Basic header line.
+fn basic(line: &str) -> Option<String> {
+    let k = match line.find(':') {
+        Some(k) => k,
+        None => return None,
+    };
+    if !line[..k].trim().eq_ignore_ascii_case("authorization") {
+        return None;
+    }
+    let v: Vec<&str> = line[k + 1..].split_whitespace().collect();
+    match &v[..] {
+        &[scheme, b] if scheme.eq_ignore_ascii_case("basic") => {
+            base64(b).map(|x| String::from_utf8_lossy(&x).into_owned())
+        }
+        _ => None,
+    }
+}
+
+const BASE64: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(s: &str) -> Option<Vec<u8>> {
+    let mut r: Vec<u8> = Vec::new();
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for c in s.trim_end_matches('=').bytes() {
+        let d = match BASE64.iter().position(|&x| x == c) {
+            Some(d) => d,
+            None => return None,
+        };
+        acc = (acc << 6) | d as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            r.push((acc >> bits) as u8);
+        }
+    }
+    Some(r)
+}
+
+fn hex(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+// Resolves %xx escapes, + stays a plus since it is far more common in k than
+// a form encoded space.
+pub fn decode(s: &str) -> String {
+    let b = s.as_bytes();
+    let mut r: Vec<u8> = Vec::new();
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'%' if i + 2 < b.len() && hex(b[i + 1]).is_some() && hex(b[i + 2]).is_some() => {
+                r.push(hex(b[i + 1]).unwrap() * 16 + hex(b[i + 2]).unwrap());
+                i += 2;
+            }
+            c => r.push(c),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&r).into_owned()
+}
+
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Writes a whole response, the connection is closed after it.
+pub fn respond(s: &mut TcpStream, status: &str, kind: &str, body: &[u8]) {
+    let challenge = if status.starts_with("401") {
+        "WWW-Authenticate: Basic realm=\"k\"\r\n"
+    } else {
+        ""
+    };
+    let head = format!("HTTP/1.1 {}\r\n{}Content-Type: {}\r\nContent-Length: {}\r\n\
+                        Connection: close\r\n\r\n",
+                       status,
+                       challenge,
+                       kind,
+                       body.len());
+    let _ = s.write_all(head.as_bytes()).and_then(|_| s.write_all(body)).and_then(|_| s.flush());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authorization() {
+        assert_eq!(basic("Authorization: Basic Ym9iOnNlY3JldA==\r\n"),
+                   Some("bob:secret".to_string()));
+        assert_eq!(basic("authorization: basic dTo=\r\n"), Some("u:".to_string()));
+        assert_eq!(basic("Authorization: Bearer abc\r\n"), None);
+        assert_eq!(basic("Host: localhost\r\n"), None);
+        assert_eq!(basic("Authorization: Basic **\r\n"), None);
+    }
+}
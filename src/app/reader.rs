@@ -0,0 +1,120 @@
+use std::io::{self, BufRead, Cursor};
+
+// Line source of a Reader. Stdin reads through its shared buffer without
+// holding the lock, so that a nested reader (the debugger) can take over.
+pub trait ReadLine {
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize>;
+}
+
+impl ReadLine for io::Stdin {
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        io::Stdin::read_line(self, buf)
+    }
+}
+
+impl<T: AsRef<[u8]>> ReadLine for Cursor<T> {
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        BufRead::read_line(self, buf)
+    }
+}
+
+pub struct Reader<R> {
+    input: R,
+}
+
+impl<R: ReadLine> Reader<R> {
+    pub fn new(input: R) -> Reader<R> {
+        Reader { input: input }
+    }
+
+    // Reads lines until brackets and strings are balanced, ps2 is called before
+    // every continuation line. Returns None at the end of input.
+    pub fn next<F: FnMut()>(&mut self, mut ps2: F) -> Option<String> {
+        let mut text = String::new();
+        loop {
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                Ok(0) | Err(_) => {
+                    return if text.is_empty() { None } else { Some(text) };
+                }
+                Ok(_) => (),
+            }
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(line.trim_end_matches(|c| c == '\n' || c == '\r'));
+            if balanced(&text) {
+                return Some(text);
+            }
+            ps2();
+        }
+    }
+}
+
+// True when every ( [ { and " opened in s is closed. Surplus closing brackets
+// count as balanced and are left for the parser to report.
+pub fn balanced(s: &str) -> bool {
+    let mut stack: Vec<char> = Vec::new();
+    let mut string = false;
+    let mut escape = false;
+    let mut prev = '\n';
+    let mut comment = false;
+    for c in s.chars() {
+        if comment {
+            if c == '\n' {
+                comment = false;
+            }
+        } else if string {
+            if escape {
+                escape = false;
+            } else if c == '\\' {
+                escape = true;
+            } else if c == '"' {
+                string = false;
+            }
+        } else {
+            match c {
+                '"' => string = true,
+                '/' if prev.is_whitespace() => comment = true,
+                '(' | '[' | '{' => stack.push(c),
+                ')' | ']' | '}' => {
+                    if stack.pop().is_none() {
+                        return true;
+                    }
+                }
+                _ => (),
+            }
+        }
+        prev = c;
+    }
+    !string && stack.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balance() {
+        for s in &["1+2", "f:{x+1}", "(1;[a:2])", "\"(\"", "\"a\\\"(\"", "1 / (", "x:1)", ""] {
+            assert!(balanced(s), "{}", s);
+        }
+        for s in &["f:{x+1", "(1;[a:2)", "\"abc", "\"a\\\"", "{x / }\n", "$[x;"] {
+            assert!(!balanced(s), "{}", s);
+        }
+    }
+
+    #[test]
+    fn continuation() {
+        let mut r = Reader::new(Cursor::new("f:{x+\n1}\n2\n\"a\nb\"\n(1;\n"));
+        let mut ps2 = 0;
+        assert_eq!(r.next(|| ps2 += 1), Some("f:{x+\n1}".to_string()));
+        assert_eq!(ps2, 1);
+        assert_eq!(r.next(|| ps2 += 1), Some("2".to_string()));
+        assert_eq!(r.next(|| ps2 += 1), Some("\"a\nb\"".to_string()));
+        assert_eq!(ps2, 2);
+        // left open at the end of input, the parser reports it
+        assert_eq!(r.next(|| ps2 += 1), Some("(1;".to_string()));
+        assert_eq!(r.next(|| ps2 += 1), None);
+    }
+}
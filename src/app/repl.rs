@@ -3,12 +3,25 @@
 
 extern crate test;
 extern crate k;
+extern crate libc;
+
+mod reader;
+mod system;
+mod golden;
+mod debug;
+mod server;
+mod http;
 
 use k::parse::ast::AST;
-use k::exec::i10;
-use std::io::{self, Read, Write};
+use k::exec::i10::{self, Interpreter};
+use std::env;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Write};
+use std::net::TcpListener;
+use std::process;
 use std::str;
-use std::ascii::AsciiExt;
+use reader::Reader;
 
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
@@ -17,33 +30,180 @@ fn ps1() {
     io::stdout().flush().unwrap();
 }
 
+fn ps2() {
+    print!("  ");
+    io::stdout().flush().unwrap();
+}
+
+fn error<E: Debug>(e: E) -> String {
+    format!("'{}", format!("{:?}", e).to_ascii_lowercase())
+}
+
+fn exec(i: &mut Interpreter, text: &str) -> Result<AST, String> {
+    if text.trim_start().starts_with('\\') {
+        return system::command(i, text.trim_start());
+    }
+    let n = try!(i.parse(text.as_bytes()).map_err(error));
+    i.run(&n).map_err(error)
+}
+
+// Runs a script top to bottom, stops at the first error.
+// Returns false if the script asked to quit.
+fn script(i: &mut Interpreter, path: &str) -> Result<bool, String> {
+    let mut text = String::new();
+    let _ = try!(File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut text))
+        .map_err(|e| format!("'{}: {}", path, e)));
+    if text.starts_with("#!") {
+        // keep the newline so that line numbers still match the file
+        let n = text.find('\n').unwrap_or(text.len());
+        text = text[n..].to_string();
+    }
+    let mut input = Reader::new(Cursor::new(text));
+    while let Some(expr) = input.next(|| ()) {
+        if expr.trim().is_empty() {
+            continue;
+        }
+        if let AST::Quit = try!(exec(i, &expr)) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// Evaluates a console input and prints the result, errors go to stderr
+// unless on a terminal. Returns None if the input asked to quit, otherwise
+// whether it succeeded.
+fn input(i: &mut Interpreter, line: &str, tty: bool) -> Option<bool> {
+    match exec(i, line) {
+        Ok(AST::Quit) => None,
+        Ok(u) => {
+            i.print(&u);
+            Some(true)
+        }
+        Err(e) => {
+            if tty {
+                println!("{}", e);
+            } else {
+                let _ = writeln!(io::stderr(), "{}", e);
+            }
+            Some(tty)
+        }
+    }
+}
+
+// Interactive when stdin is a terminal, otherwise banner and prompts are
+// suppressed and errors go to stderr. Returns false if any input failed.
+fn repl(i: &mut Interpreter, tty: bool) -> bool {
+    let mut input = Reader::new(io::stdin());
+    let mut ok = true;
+    if tty {
+        ps1();
+    }
+    loop {
+        let line = match input.next(|| if tty { ps2() }) {
+            Some(l) => l,
+            None => break,
+        };
+        match ::input(i, &line, tty) {
+            Some(x) => ok = ok && x,
+            None => break,
+        }
+        if tty {
+            ps1();
+        }
+    }
+    ok
+}
+
+// Interface and port of -p [host:]port, the loopback one unless a host is
+// given, 0.0.0.0 listens on every interface.
+fn address(s: &str) -> Option<(&str, u16)> {
+    let (host, port) = match s.rfind(':') {
+        Some(k) => (&s[..k], &s[k + 1..]),
+        None => ("127.0.0.1", s),
+    };
+    match port.parse::<u16>() {
+        Ok(n) if !host.is_empty() => Some((host, n)),
+        _ => None,
+    }
+}
+
+fn usage() -> ! {
+    println!("usage: k [-i] [-p [host:]port] [--profile] [script.k [args...]]\n       k --test path");
+    process::exit(1);
+}
+
+fn exit(i: &Interpreter, profile: bool, code: i32) -> ! {
+    if profile {
+        system::profile(i);
+    }
+    process::exit(code)
+}
+
 fn main() {
     let mut i = i10::new();
-    let mut input = vec![0u8; 256];
-    println!("Welcome to O lang v{} interpreter...", VERSION);
-    ps1();
-    loop {
-        let size = io::stdin().read(&mut input).expect("STDIN error.");
-        let k = i.parse(&input[..size - 1]);
-        match k {
-            Ok(n) => {
-                // println!("------ Parse ------ \n{:#?}", n);
-                match i.run(&n) {
-                    Ok(x) => {
-                        match x {
-                            AST::Quit => break,
-                            u => {
-                                i.print(&u);
-                            }
-                        }
+    let tty = unsafe { libc::isatty(libc::STDIN_FILENO) != 0 };
+    let mut interactive = false;
+    let mut listener: Option<TcpListener> = None;
+    let mut profile = false;
+    let mut argv = env::args().skip(1);
+    let mut file: Option<String> = None;
+    while let Some(a) = argv.next() {
+        match &a[..] {
+            "-i" => interactive = true,
+            "--profile" => profile = true,
+            "-p" => {
+                let port = argv.next().unwrap_or_else(|| usage());
+                let (host, n) = address(&port).unwrap_or_else(|| usage());
+                match TcpListener::bind((host, n)) {
+                    Ok(l) => listener = Some(l),
+                    Err(e) => {
+                        let _ = writeln!(io::stderr(), "'{}: {}", port, e);
+                        process::exit(1);
+                    }
+                }
+            }
+            "--test" => {
+                let path = argv.next().unwrap_or_else(|| usage());
+                process::exit(if golden::run(&path) { 0 } else { 1 });
+            }
+            _ if a.starts_with('-') => usage(),
+            _ => {
+                file = Some(a);
+                break;
+            }
+        }
+    }
+    // the debugger reads the console itself, which a server leaves to a thread
+    if tty && listener.is_none() {
+        i.set_hook(Some(debug::hook));
+    }
+    let rest: Vec<String> = argv.collect();
+    let args = i.strings(&rest).expect("Argument is too long.");
+    i.set("args", args);
+    i.set_profile(profile);
+    match file {
+        Some(f) => {
+            match script(&mut i, &f) {
+                Ok(true) if interactive || listener.is_some() => (),
+                Ok(_) => exit(&i, profile, 0),
+                Err(e) => {
+                    let _ = writeln!(io::stderr(), "{}", e);
+                    if !interactive {
+                        exit(&i, profile, 1);
                     }
-                    Err(e) => println!("'{}", format!("{:?}", e).to_ascii_lowercase()),
                 }
             }
-            Err(e) => println!("'{}", format!("{:?}", e).to_ascii_lowercase()),
         }
-        ps1();
+        None if tty => println!("Welcome to O lang v{} interpreter...", VERSION),
+        None => (),
     }
+    let ok = match listener {
+        Some(l) => server::serve(&mut i, l, tty),
+        None => repl(&mut i, tty),
+    };
+    exit(&i, profile, if ok { 0 } else { 1 });
 }
 
 #[cfg(test)]
@@ -51,6 +211,25 @@ mod tests {
     use super::*;
     use test::Bencher;
 
+    #[test]
+    fn listen_address() {
+        assert_eq!(address("5001"), Some(("127.0.0.1", 5001)));
+        assert_eq!(address("0.0.0.0:5001"), Some(("0.0.0.0", 5001)));
+        assert_eq!(address(":5001"), None);
+        assert_eq!(address("x"), None);
+    }
+
+    // Without a terminal a failed input makes the run fail, with one the
+    // session goes on.
+    #[test]
+    fn pipe_input() {
+        let mut i = i10::new();
+        assert_eq!(input(&mut i, "a:1+2", false), Some(true));
+        assert_eq!(input(&mut i, "a+`b", false), Some(false));
+        assert_eq!(input(&mut i, "a+`b", true), Some(true));
+        assert_eq!(input(&mut i, "\\\\", false), None);
+    }
+
     #[bench]
     fn fac_k(b: &mut Bencher) {
         let mut i = i10::new();
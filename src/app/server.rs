@@ -0,0 +1,276 @@
+use k::parse::ast::AST;
+use k::exec::error::Error as ExecError;
+use k::exec::i10::Interpreter;
+use k::exec::ipc::{self, Kind, Message};
+use std::collections::HashMap;
+use std::io;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use reader::Reader;
+use http;
+
+// The interpreter stays on the main thread, console input and every
+// connection are read on threads of their own and handed over as events.
+enum Event {
+    Line(Option<String>),
+    Open(usize, TcpStream),
+    Message(usize, Message),
+    Http(usize, http::Request),
+    Close(usize),
+}
+
+fn console(tty: bool, tx: Sender<Event>) {
+    let mut input = Reader::new(io::stdin());
+    loop {
+        let l = input.next(|| if tty { ::ps2() });
+        let end = l.is_none();
+        if tx.send(Event::Line(l)).is_err() || end {
+            return;
+        }
+    }
+}
+
+fn listen(l: TcpListener, tx: Sender<Event>) {
+    for (n, s) in l.incoming().enumerate() {
+        let s = match s {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+        let _ = s.set_nodelay(true);
+        let w = match s.try_clone() {
+            Ok(w) => w,
+            Err(_) => continue,
+        };
+        if tx.send(Event::Open(n, w)).is_err() {
+            return;
+        }
+        let tx = tx.clone();
+        thread::spawn(move || connection(n, s, tx));
+    }
+}
+
+fn connection(n: usize, mut s: TcpStream, tx: Sender<Event>) {
+    let mut b = [0u8; 4];
+    if let Ok(4) = s.peek(&mut b) {
+        if &b == b"GET " {
+            let e = match http::request(&s) {
+                Some(t) => Event::Http(n, t),
+                None => Event::Close(n),
+            };
+            let _ = tx.send(e);
+            return;
+        }
+    }
+    loop {
+        let e = match ipc::recv(&mut s) {
+            Ok(m) => Event::Message(n, m),
+            Err(_) => Event::Close(n),
+        };
+        let close = match e {
+            Event::Close(_) => true,
+            _ => false,
+        };
+        if tx.send(e).is_err() || close {
+            return;
+        }
+    }
+}
+
+// Accepted connection, the user is known once the login passed.
+struct Conn {
+    stream: TcpStream,
+    user: Option<String>,
+}
+
+// Handlers are optional globals, called with zw set to the connection number
+// and zu to the user symbol:
+//   zpw[user;password]  login, accepted when true, everyone without zpw
+//   zpo[w] zpc[w]       connection opened and closed
+//   zpg[x] zps[x]       sync and async message, execute without them
+fn hook(i: &mut Interpreter,
+        name: &str,
+        n: usize,
+        user: &str,
+        args: &[AST])
+        -> Option<Result<AST, ExecError>> {
+    let u = i.symbol(user);
+    i.set("zw", AST::Int { value: n as i64 });
+    i.set("zu", u);
+    i.hook(name, args)
+}
+
+// User of user:password when zpw accepts it or is not defined.
+fn authorize(i: &mut Interpreter, n: usize, login: &str) -> Result<String, ExecError> {
+    let (user, password) = match login.find(':') {
+        Some(k) => (&login[..k], &login[k + 1..]),
+        None => (login, ""),
+    };
+    let u = i.symbol(user);
+    let p = i.string(password);
+    match hook(i, "zpw", n, user, &[u, p]) {
+        None => Ok(user.to_string()),
+        Some(Ok(ref x)) if i.truth(x) => Ok(user.to_string()),
+        _ => Err(ExecError::Access),
+    }
+}
+
+fn login(i: &mut Interpreter, n: usize, c: &mut Conn, m: Message) {
+    let r = match m.kind {
+        Kind::Login => authorize(i, n, &String::from_utf8_lossy(&m.payload)),
+        _ => Err(ExecError::Access),
+    };
+    let user = match r {
+        Ok(u) => u,
+        Err(e) => {
+            let _ = ipc::send(&mut c.stream, Kind::Response, true, e.name().as_bytes());
+            let _ = c.stream.shutdown(Shutdown::Both);
+            return;
+        }
+    };
+    let _ = ipc::send(&mut c.stream, Kind::Response, false, &[]);
+    let _ = hook(i, "zpo", n, &user, &[AST::Int { value: n as i64 }]);
+    c.user = Some(user);
+}
+
+// Evaluates a message, only sync ones get the result or the error back.
+fn reply(i: &mut Interpreter, n: usize, c: &mut Conn, m: Message) {
+    let user = match c.user {
+        Some(ref u) => u.clone(),
+        None => return login(i, n, c, m),
+    };
+    let name = match m.kind {
+        Kind::Sync => "zpg",
+        Kind::Async => "zps",
+        _ => return,
+    };
+    let r = match i.deserialize_value(&m.payload) {
+        Ok(x) => {
+            match hook(i, name, n, &user, &[x]) {
+                Some(r) => r,
+                None => i.execute(&x),
+            }
+        }
+        Err(e) => Err(e),
+    };
+    if m.kind != Kind::Sync {
+        return;
+    }
+    let _ = match r.and_then(|x| i.serialize(&x)) {
+        Ok(b) => ipc::send(&mut c.stream, Kind::Response, false, &b),
+        Err(e) => ipc::send(&mut c.stream, Kind::Response, true, e.name().as_bytes()),
+    };
+}
+
+fn html(text: &str) -> String {
+    format!("<html><head><title>k</title></head><body><pre>{}</pre></body></html>",
+            http::escape(text))
+}
+
+// GET /?expr answers the result of expr formatted in a html page, /json?expr
+// as JSON. A request logs in through zpw with the credentials of its basic
+// authorization and the expression runs like a sync message, through zpg when
+// defined. zph[x] replaces zpg and the formatting for both paths, it is
+// called with the expression and returns the page as text or bytes.
+fn page(i: &mut Interpreter, n: usize, s: &mut TcpStream, req: &http::Request) {
+    let target = &req.target[..];
+    let (path, query) = match target.find('?') {
+        Some(k) => (&target[..k], http::decode(&target[k + 1..])),
+        None => (target, String::new()),
+    };
+    let (json, kind) = match path {
+        "/" => (false, "text/html"),
+        "/json" => (true, "application/json"),
+        _ => return http::respond(s, "404 Not Found", "text/plain", b"not found"),
+    };
+    let user = match authorize(i, n, &req.login) {
+        Ok(u) => u,
+        Err(e) => return http::respond(s, "401 Unauthorized", "text/plain", e.name().as_bytes()),
+    };
+    let q = i.string(&query);
+    if let Some(r) = hook(i, "zph", n, &user, &[q]) {
+        let body = r.and_then(|x| match i.text(&x) {
+            Ok(l) => Ok(l.join("\n").into_bytes()),
+            Err(_) => i.bytes(&x),
+        });
+        return match body {
+            Ok(b) => http::respond(s, "200 OK", kind, &b),
+            Err(e) => http::respond(s, "400 Bad Request", "text/plain", e.name().as_bytes()),
+        };
+    }
+    let r = match hook(i, "zpg", n, &user, &[q]) {
+        Some(r) => r,
+        None => i.execute(&q),
+    };
+    let r = match r.map_err(|e| format!("'{}", e.name())) {
+        Ok(x) if json => i.to_json(&x).map_err(|e| format!("'{}", e.name())),
+        Ok(x) => Ok(html(&i.format(&x))),
+        Err(e) => Err(e),
+    };
+    match (r, json) {
+        (Ok(t), true) => http::respond(s, "200 OK", "application/json", t.as_bytes()),
+        (Ok(t), false) => http::respond(s, "200 OK", "text/html", t.as_bytes()),
+        (Err(e), true) => {
+            let t = format!("{{\"error\":\"{}\"}}", &e[1..]);
+            http::respond(s, "400 Bad Request", "application/json", t.as_bytes())
+        }
+        (Err(e), false) => http::respond(s, "400 Bad Request", "text/html", html(&e).as_bytes()),
+    }
+}
+
+// REPL that also answers other processes connecting to l. The end of console
+// input quits a terminal session, otherwise the process keeps serving.
+// Returns false if any console input failed.
+pub fn serve(i: &mut Interpreter, l: TcpListener, tty: bool) -> bool {
+    let (tx, rx) = channel();
+    let t = tx.clone();
+    thread::spawn(move || listen(l, t));
+    thread::spawn(move || console(tty, tx));
+    let mut conns: HashMap<usize, Conn> = HashMap::new();
+    let mut ok = true;
+    if tty {
+        ::ps1();
+    }
+    for e in rx.iter() {
+        match e {
+            Event::Line(Some(line)) => {
+                match ::input(i, &line, tty) {
+                    Some(x) => ok = ok && x,
+                    None => break,
+                }
+                if tty {
+                    ::ps1();
+                }
+            }
+            Event::Line(None) => {
+                if tty {
+                    break;
+                }
+            }
+            Event::Open(n, s) => {
+                conns.insert(n,
+                             Conn {
+                                 stream: s,
+                                 user: None,
+                             });
+            }
+            Event::Message(n, m) => {
+                if let Some(c) = conns.get_mut(&n) {
+                    reply(i, n, c, m);
+                }
+            }
+            Event::Http(n, t) => {
+                if let Some(mut c) = conns.remove(&n) {
+                    page(i, n, &mut c.stream, &t);
+                    let _ = c.stream.shutdown(Shutdown::Both);
+                }
+            }
+            Event::Close(n) => {
+                if let Some(Conn { user: Some(u), .. }) = conns.remove(&n) {
+                    let _ = hook(i, "zpc", n, &u, &[AST::Int { value: n as i64 }]);
+                }
+            }
+        }
+    }
+    ok
+}
@@ -0,0 +1,204 @@
+use k::parse::ast::AST;
+use k::exec::i10::Interpreter;
+use std::env;
+use std::process::Command;
+use std::time::Instant;
+
+// Backslash commands, line starts with '\'.
+pub fn command(i: &mut Interpreter, line: &str) -> Result<AST, String> {
+    let line = line[1..].trim();
+    let (cmd, arg) = match line.find(' ') {
+        Some(n) => (&line[..n], line[n + 1..].trim()),
+        None => (line, ""),
+    };
+    match cmd {
+        "\\" => Ok(AST::Quit),
+        "t" => {
+            let start = Instant::now();
+            let _ = try!(::exec(i, arg));
+            let d = start.elapsed();
+            println!("{}", d.as_secs() * 1000 + (d.subsec_nanos() / 1000000) as u64);
+            Ok(AST::Nil)
+        }
+        "v" => {
+            println!("{}", i.names().join(" "));
+            Ok(AST::Nil)
+        }
+        "l" => {
+            match try!(::script(i, arg)) {
+                true => Ok(AST::Nil),
+                false => Ok(AST::Quit),
+            }
+        }
+        "save" | "load" => {
+            if arg.is_empty() {
+                return Err("'rank".to_string());
+            }
+            let r = if cmd == "save" { i.save(arg) } else { i.load(arg) };
+            try!(r.map_err(::error));
+            Ok(AST::Nil)
+        }
+        "w" => {
+            let (arena, (nodes, items)) = i.memory();
+            println!("arena {} env {} {}", arena, nodes, items);
+            Ok(AST::Nil)
+        }
+        "cd" => {
+            if arg.is_empty() {
+                let d = try!(env::current_dir().map_err(|e| format!("'{}", e)));
+                println!("{}", d.display());
+            } else {
+                try!(env::set_current_dir(arg).map_err(|e| format!("'{}: {}", arg, e)));
+            }
+            Ok(AST::Nil)
+        }
+        "c" => {
+            let c = i.console();
+            match &try!(numbers(arg))[..] {
+                &[] => println!("{} {}", c.rows, c.cols),
+                &[rows, cols] => i.set_console(rows, cols),
+                _ => return Err("'rank".to_string()),
+            }
+            Ok(AST::Nil)
+        }
+        "P" => {
+            let c = i.console();
+            match &try!(numbers(arg))[..] {
+                &[] => println!("{}", c.precision),
+                &[p] => i.set_precision(p),
+                _ => return Err("'rank".to_string()),
+            }
+            Ok(AST::Nil)
+        }
+        "b" => {
+            if arg.is_empty() {
+                println!("{}", i.breakpoints().join(" "));
+            } else {
+                i.set_breakpoint(arg, true);
+            }
+            Ok(AST::Nil)
+        }
+        "bd" => {
+            i.set_breakpoint(arg, false);
+            Ok(AST::Nil)
+        }
+        "e" => {
+            match &try!(numbers(arg))[..] {
+                &[e] => i.set_suspend_on_error(e != 0),
+                _ => return Err("'rank".to_string()),
+            }
+            Ok(AST::Nil)
+        }
+        "prof" => {
+            match &try!(numbers(arg))[..] {
+                &[] => profile(i),
+                &[p] => i.set_profile(p != 0),
+                _ => return Err("'rank".to_string()),
+            }
+            Ok(AST::Nil)
+        }
+        "trace" => {
+            match arg {
+                "" | "1" => i.set_trace(true, &[]),
+                "0" => i.set_trace(false, &[]),
+                _ => i.set_trace(true, &arg.split_whitespace().collect::<Vec<&str>>()),
+            }
+            Ok(AST::Nil)
+        }
+        _ => shell(line),
+    }
+}
+
+// Any other command goes to sh with the console as its input and output.
+fn shell(line: &str) -> Result<AST, String> {
+    let s = try!(Command::new("sh")
+        .arg("-c")
+        .arg(line)
+        .status()
+        .map_err(|e| format!("'{}", e)));
+    match s.code() {
+        Some(0) => Ok(AST::Nil),
+        c => Err(format!("'exit({})", c.unwrap_or(-1))),
+    }
+}
+
+fn numbers(arg: &str) -> Result<Vec<usize>, String> {
+    let mut r: Vec<usize> = Vec::new();
+    for a in arg.split_whitespace() {
+        r.push(try!(a.parse::<usize>().map_err(|_| "'type".to_string())));
+    }
+    Ok(r)
+}
+
+pub fn profile(i: &Interpreter) {
+    println!("{:<16}{:>10}{:>14}{:>14}{:>10}",
+             "name",
+             "calls",
+             "incl ms",
+             "excl ms",
+             "alloc");
+    for (n, e) in i.profile() {
+        println!("{:<16}{:>10}{:>14.3}{:>14.3}{:>10}",
+                 n,
+                 e.calls,
+                 e.inclusive as f64 / 1e6,
+                 e.exclusive as f64 / 1e6,
+                 e.alloc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k::exec::i10;
+    use k::exec::value::Value;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn settings() {
+        let mut i = i10::new();
+        command(&mut i, "\\c 10 40").unwrap();
+        let c = i.console();
+        assert_eq!((c.rows, c.cols), (10, 40));
+        assert_eq!(command(&mut i, "\\c 10").unwrap_err(), "'rank");
+        assert_eq!(command(&mut i, "\\c a b").unwrap_err(), "'type");
+        command(&mut i, "\\P 3").unwrap();
+        assert_eq!(i.console().precision, 3);
+        command(&mut i, "\\b f").unwrap();
+        command(&mut i, "\\b g").unwrap();
+        command(&mut i, "\\bd f").unwrap();
+        assert_eq!(i.breakpoints(), vec!["g"]);
+        assert_eq!(command(&mut i, "\\e").unwrap_err(), "'rank");
+        command(&mut i, "\\prof 1").unwrap();
+        i.eval_str("1+2").unwrap();
+        assert!(i.profile().iter().any(|&(ref n, ref e)| n == "+" && e.calls == 1));
+        match command(&mut i, "\\\\") {
+            Ok(AST::Quit) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn scripts_and_shell() {
+        let mut i = i10::new();
+        match command(&mut i, "\\t 1+2") {
+            Ok(AST::Nil) => (),
+            r => panic!("{:?}", r),
+        }
+        assert_eq!(command(&mut i, "\\t 1+`a").unwrap_err(), "'type");
+        assert!(command(&mut i, "\\l /nonexistent.k").unwrap_err().starts_with("'/nonexistent.k"));
+        assert!(command(&mut i, "\\true").is_ok());
+        assert_eq!(command(&mut i, "\\exit 3").unwrap_err(), "'exit(3)");
+        let path = env::temp_dir().join(format!("k-cmd-{}", process::id()));
+        let p = path.to_str().unwrap();
+        i.eval_str("a:1 2").unwrap();
+        command(&mut i, &format!("\\save {}", p)).unwrap();
+        let mut j = i10::new();
+        command(&mut j, &format!("\\load {}", p)).unwrap();
+        assert_eq!(j.eval_str("a").unwrap(), Value::Ints(vec![1, 2]));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(command(&mut i, "\\save").unwrap_err(), "'rank");
+        assert_eq!(command(&mut i, "\\load").unwrap_err(), "'rank");
+    }
+}
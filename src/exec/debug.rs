@@ -0,0 +1,160 @@
+use parse::ast::AST;
+use exec::error::Error as ExecError;
+use exec::i10::Interpreter;
+use exec::otree;
+
+// Lambda activation, name is the global it was called through if any.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    pub name: Option<u16>,
+    pub lambda: AST,
+    pub env: otree::Id,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    Run,
+    Into,
+    Over(usize), // stop again at this frame depth or above
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Stop {
+    Call,
+    Statement(AST),
+    Error(ExecError),
+}
+
+// Called with the interpreter suspended, the innermost frame is the last one
+// of Interpreter::backtrace. Returns how to resume.
+pub type Hook = fn(&mut Interpreter, Stop) -> Step;
+
+pub struct Debugger {
+    pub breakpoints: Vec<u16>,
+    pub on_error: bool,
+    pub step: Step,
+    pub hook: Option<Hook>,
+    unwinding: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            breakpoints: Vec::new(),
+            on_error: false,
+            step: Step::Run,
+            hook: None,
+            unwinding: false,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.step = Step::Run;
+        self.unwinding = false;
+    }
+
+    // Whether the interpreter at frame depth should suspend on stop.
+    pub fn hit(&mut self, stop: &Stop, frame: Option<&Frame>, depth: usize) -> bool {
+        if self.hook.is_none() {
+            return false;
+        }
+        match *stop {
+            Stop::Error(_) => {
+                if self.on_error && !self.unwinding {
+                    self.unwinding = true;
+                    return true;
+                }
+                false
+            }
+            Stop::Call if frame.and_then(|f| f.name)
+                .map_or(false, |n| self.breakpoints.contains(&n)) => true,
+            _ => {
+                match self.step {
+                    Step::Run => false,
+                    Step::Into => true,
+                    Step::Over(d) => depth <= d,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+    use exec::i10;
+    use exec::value::Value;
+
+    static STEPS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    // Leaves the kind of stop, the depth and x of the innermost frame in
+    // globals, then resumes.
+    fn record(i: &mut Interpreter, stop: Stop) -> Step {
+        let depth = i.backtrace().len();
+        let s = match stop {
+            Stop::Call => "call".to_string(),
+            Stop::Statement(_) => "statement".to_string(),
+            Stop::Error(e) => e.name(),
+        };
+        let s = i.symbol(&s);
+        i.set("stop", s);
+        i.set("depth", AST::Int { value: depth as i64 });
+        let x = i.parse(b"x").unwrap();
+        if let Ok(v) = i.run_at(&x, depth - 1) {
+            i.set("arg", v);
+        }
+        Step::Run
+    }
+
+    fn step(_: &mut Interpreter, _: Stop) -> Step {
+        STEPS.fetch_add(1, Ordering::SeqCst);
+        Step::Into
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut i = i10::new();
+        i.set_hook(Some(record));
+        i.eval_str("f:{[x]x+1}").unwrap();
+        i.eval_str("g:{[x]f x*2}").unwrap();
+        i.set_breakpoint("f", true);
+        assert_eq!(i.eval_str("g 3").unwrap(), Value::Int(7));
+        assert_eq!(i.eval_str("stop").unwrap(), Value::Symbol("call".to_string()));
+        assert_eq!(i.eval_str("depth").unwrap(), Value::Int(2));
+        assert_eq!(i.eval_str("arg").unwrap(), Value::Int(6));
+        i.set_breakpoint("f", false);
+        i.eval_str("stop:`none").unwrap();
+        assert_eq!(i.eval_str("g 3").unwrap(), Value::Int(7));
+        assert_eq!(i.eval_str("stop").unwrap(), Value::Symbol("none".to_string()));
+    }
+
+    #[test]
+    fn errors() {
+        let mut i = i10::new();
+        i.set_hook(Some(record));
+        i.eval_str("h:{[x]x+`a}").unwrap();
+        assert!(i.eval_str("h 5").is_err());
+        assert!(i.eval_str("stop").is_err());
+        i.set_suspend_on_error(true);
+        assert_eq!(i.eval_str("h 5").unwrap_err().to_string(), "'type");
+        assert_eq!(i.eval_str("stop").unwrap(), Value::Symbol("type".to_string()));
+        assert_eq!(i.eval_str("depth").unwrap(), Value::Int(1));
+        assert_eq!(i.eval_str("arg").unwrap(), Value::Int(5));
+    }
+
+    // Stepping into a call stops again at every statement of its body.
+    #[test]
+    fn steps() {
+        let mut i = i10::new();
+        i.set_hook(Some(step));
+        i.eval_str("f:{[x]a:x;b:a+1;b}").unwrap();
+        i.set_breakpoint("f", true);
+        assert_eq!(i.eval_str("f 1").unwrap(), Value::Int(2));
+        assert!(STEPS.load(Ordering::SeqCst) >= 4);
+        i.set_breakpoint("f", false);
+        let n = STEPS.load(Ordering::SeqCst);
+        assert_eq!(i.eval_str("f 1").unwrap(), Value::Int(2));
+        assert_eq!(STEPS.load(Ordering::SeqCst), n);
+    }
+}
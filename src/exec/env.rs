@@ -16,6 +16,11 @@ impl Environment {
         Environment { tree: s }
     }
 
+    // The global environment.
+    pub fn root(&self) -> otree::Id {
+        self.tree.root()
+    }
+
     pub fn last(&self) -> otree::Id {
         self.tree.last()
     }
@@ -32,8 +37,8 @@ impl Environment {
         self.tree.append_node(n)
     }
 
-    pub fn define(&mut self, key: u16, value: ast::Id) {
-        self.tree.insert(Entry(key, value));
+    pub fn define(&mut self, key: u16, value: ast::Id, n: otree::Id) {
+        self.tree.insert(n, Entry(key, value));
     }
 
     pub fn get(&self, key: u16, n: otree::Id) -> Option<(ast::Id, otree::Id)> {
@@ -43,7 +48,71 @@ impl Environment {
         }
     }
 
+    // Values of the names visible from n, shadowed ones excluded.
+    pub fn values(&self, n: otree::Id) -> Vec<ast::Id> {
+        let mut keys: Vec<u16> = Vec::new();
+        let mut r: Vec<ast::Id> = Vec::new();
+        for e in self.tree.scope(n) {
+            if !keys.contains(&e.0) {
+                keys.push(e.0);
+                r.push(e.1);
+            }
+        }
+        r
+    }
+
     pub fn clean(&mut self) -> usize {
         self.tree.clean()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A local shadows a global of the same name only inside its call.
+    #[test]
+    fn locals() {
+        let mut e = Environment::new_root();
+        let root = e.root();
+        e.define(1, 10, root);
+        let f = e.new_child(root);
+        e.define(1, 20, f);
+        e.define(2, 30, f);
+        assert_eq!(e.get(1, f), Some((20, f)));
+        assert_eq!(e.get(1, root), Some((10, root)));
+        assert_eq!(e.get(2, root), None);
+        assert_eq!(e.values(f), vec![30, 20]);
+        assert_eq!(e.values(root), vec![10]);
+    }
+
+    // A global assigned while a call is running is visible to the call and
+    // stays after it.
+    #[test]
+    fn globals() {
+        let mut e = Environment::new_root();
+        let root = e.root();
+        let f = e.new_child(root);
+        let g = e.new_child(f);
+        e.define(1, 10, root);
+        assert_eq!(e.get(1, g), Some((10, root)));
+        e.define(1, 11, root);
+        assert_eq!(e.get(1, f), Some((11, root)));
+        assert_eq!(e.values(root), vec![11]);
+    }
+
+    #[test]
+    fn clean() {
+        let mut e = Environment::new_root();
+        let root = e.root();
+        e.define(1, 10, root);
+        let f = e.new_child(root);
+        e.define(2, 20, f);
+        assert_eq!(e.clean(), 2);
+        assert_eq!(e.len(), (1, 0));
+        assert_eq!(e.last(), e.root());
+        assert_eq!(e.get(1, root), None);
+        e.define(1, 12, root);
+        assert_eq!(e.get(1, root), Some((12, root)));
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Error {
     RuntimeError,
     Rank,
@@ -12,4 +12,40 @@ pub enum Error {
     NotImplemented,
     InvalidType,
     InvalidNativeCall,
-}
\ No newline at end of file
+    Io,
+    Malformed,
+    Version,
+    Access,
+    Exit(i32),
+}
+
+const ALL: [Error; 16] = [Error::RuntimeError,
+                          Error::Rank,
+                          Error::Type,
+                          Error::Length,
+                          Error::Condition,
+                          Error::Call,
+                          Error::Undefined,
+                          Error::Stack,
+                          Error::InvalidString,
+                          Error::NotImplemented,
+                          Error::InvalidType,
+                          Error::InvalidNativeCall,
+                          Error::Io,
+                          Error::Malformed,
+                          Error::Version,
+                          Error::Access];
+
+impl Error {
+    // Lower case name as printed after a quote, 'type.
+    pub fn name(&self) -> String {
+        format!("{:?}", self).to_lowercase()
+    }
+
+    pub fn from_name(s: &str) -> Option<Error> {
+        if s.starts_with("exit(") && s.ends_with(')') {
+            return s[5..s.len() - 1].parse::<i32>().ok().map(Error::Exit);
+        }
+        ALL.iter().find(|e| e.name() == s).map(|e| *e)
+    }
+}
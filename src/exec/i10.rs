@@ -1,19 +1,38 @@
-use parse::ast::{self, AST};
+use parse::ast::{self, AST, Console};
 use parse::parser::{self, Parser};
 use parse::error::Error as ParseError;
 use exec::error::Error as ExecError;
 use exec::env::Environment;
+use exec::debug::{Debugger, Frame, Hook, Step, Stop};
+use exec::profile::{Entry, Key, Profiler};
+use exec::trace::Tracer;
+use exec::io::{self, Column, Target};
+use exec::serial;
+use exec::json;
+use exec::ipc::{self, Handles, Kind};
+use exec::splay;
+use exec::value::{self, Function, Value};
 use parse::alloc::Arena;
 use parse::vector::Vector;
+use parse::temporal;
 use exec::otree;
 use stacker;
 use handle;
 use std::i8::MAX;
+use std::env;
+use std::path::Path;
 
 pub struct Interpreter {
     parser: Parser,
     arena: Arena,
     env: Environment,
+    frames: Vec<Frame>,
+    callee: Option<u16>,
+    debugger: Debugger,
+    profiler: Profiler,
+    tracer: Tracer,
+    handles: Handles,
+    mapped: usize,
 }
 
 impl Interpreter {
@@ -29,13 +48,13 @@ impl Interpreter {
     }
 
     pub fn parse_ast(&mut self, ast: &AST) -> Result<AST, ExecError> {
-        match *ast {
-            AST::String { value: s } => {
+        match self.arena.string_of(ast) {
+            Some(s) => {
                 self.parser
-                    .parse_str(&s.to_string()[..], &mut self.arena)
+                    .parse_str(&s, &mut self.arena)
                     .map_err(|_| ExecError::InvalidNativeCall)
             }
-            _ => Err(ExecError::InvalidNativeCall),
+            None => Err(ExecError::InvalidNativeCall),
         }
     }
 
@@ -44,6 +63,16 @@ impl Interpreter {
             (&AST::Int { value: a }, &AST::Int { value: b }) => {
                 return Ok(AST::Int { value: a + b })
             }
+            (&AST::Temporal { kind: k, value: a }, &AST::Int { value: b }) |
+            (&AST::Int { value: b }, &AST::Temporal { kind: k, value: a }) => {
+                return Ok(AST::Temporal {
+                    kind: k,
+                    value: if a == ast::NULL || b == ast::NULL { ast::NULL } else { a + b },
+                })
+            }
+            (&AST::Temporal { kind: k, value: a }, &AST::Temporal { kind: l, value: b }) => {
+                return temporal::add((k, a), (l, b)).ok_or(ExecError::Type)
+            }
             (&AST::List { curry: true, values: ref a }, &AST::Int { value: b }) => {
                 let mut r: Vec<AST> = Vec::new();
                 let (s1, s2) = handle::split(self);
@@ -82,6 +111,15 @@ impl Interpreter {
             (&AST::Int { value: a }, &AST::Int { value: b }) => {
                 return Ok(AST::Int { value: a - b })
             }
+            (&AST::Temporal { kind: k, value: a }, &AST::Int { value: b }) => {
+                return Ok(AST::Temporal {
+                    kind: k,
+                    value: if a == ast::NULL || b == ast::NULL { ast::NULL } else { a - b },
+                })
+            }
+            (&AST::Temporal { kind: k, value: a }, &AST::Temporal { kind: l, value: b }) => {
+                return temporal::sub((k, a), (l, b)).ok_or(ExecError::Type)
+            }
             (&AST::List { curry: true, values: ref a }, &AST::Int { value: b }) => {
                 let mut r: Vec<AST> = Vec::new();
                 let (s1, s2) = handle::split(self);
@@ -158,11 +196,140 @@ impl Interpreter {
             (&AST::Int { value: a }, &AST::Int { value: b }) => {
                 return Ok(AST::Bool { value: a == b })
             }
+            (&AST::Temporal { kind: k, value: a },
+             &AST::Temporal { kind: l, value: b }) if k == l => {
+                return Ok(AST::Bool { value: a == b })
+            }
+            (&AST::Symbol { value: a }, &AST::Symbol { value: b }) => {
+                return Ok(AST::Bool { value: a == b })
+            }
+            _ => (),
+        };
+        match (self.arena.string_of(left), self.arena.string_of(right)) {
+            (Some(a), Some(b)) => Ok(AST::Bool { value: a == b }),
+            _ => Err(ExecError::Type),
+        }
+    }
+
+    fn less(&mut self, left: &AST, right: &AST, _: otree::Id) -> Result<AST, ExecError> {
+        match (left, right) {
+            (&AST::Int { value: a }, &AST::Int { value: b }) => {
+                return Ok(AST::Bool { value: a < b })
+            }
+            (&AST::Temporal { kind: k, value: a },
+             &AST::Temporal { kind: l, value: b }) if k == l => {
+                return Ok(AST::Bool { value: a < b })
+            }
+            _ => (),
+        };
+        Err(ExecError::Type)
+    }
+
+    fn more(&mut self, left: &AST, right: &AST, _: otree::Id) -> Result<AST, ExecError> {
+        match (left, right) {
+            (&AST::Int { value: a }, &AST::Int { value: b }) => {
+                return Ok(AST::Bool { value: a > b })
+            }
+            (&AST::Temporal { kind: k, value: a },
+             &AST::Temporal { kind: l, value: b }) if k == l => {
+                return Ok(AST::Bool { value: a > b })
+            }
             _ => (),
         };
         Err(ExecError::Type)
     }
 
+    // The list of a mapped column, copied into the arena once per column.
+    fn materialize(&mut self, ast: &AST) -> AST {
+        match *ast {
+            AST::Mapped { id: n } => {
+                if let Some(l) = self.arena.column(n).list {
+                    return l;
+                }
+                let v = self.arena.column(n).items();
+                let l = ast::list(true, &mut self.arena.ast, v);
+                if let Some(ref mut m) = self.arena.mapped[n as usize] {
+                    m.list = Some(l);
+                }
+                l
+            }
+            _ => *ast,
+        }
+    }
+
+    // Unmaps the columns that no variable and no opened partition refers to.
+    // Only run between top level evaluations, when every value in use is held
+    // by a global, and only after get has mapped new columns.
+    fn release(&mut self) {
+        if self.arena.mapped.len() == self.mapped {
+            return;
+        }
+        self.mapped = self.arena.mapped.len();
+        let mut live = vec![false; self.mapped];
+        let id = self.env.root();
+        let mut todo: Vec<AST> = self.env
+            .values(id)
+            .iter()
+            .map(|n| *self.arena.ast.deref(*n))
+            .collect();
+        for p in &self.arena.partitioned {
+            todo.extend(p.loaded.iter().filter_map(|t| *t));
+        }
+        while let Some(x) = todo.pop() {
+            let a = &self.arena.ast;
+            match x {
+                AST::Mapped { id: n } => live[n as usize] = true,
+                AST::Verb { args: ref v, .. } |
+                AST::List { values: ref v, .. } |
+                AST::Sequence { values: ref v } |
+                AST::Condition { list: ref v } |
+                AST::Dict { values: ref v, .. } |
+                AST::Table { values: ref v, .. } => todo.extend(v.iter(a).filter(|x| !x.is_atom())),
+                AST::Lambda { body: b, .. } |
+                AST::Nameref { value: b, .. } |
+                AST::Debug { value: b } => todo.push(*a.deref(b)),
+                AST::Adverb { left: l, verb: v, right: r, .. } => {
+                    todo.extend(&[*a.deref(l), *a.deref(v), *a.deref(r)])
+                }
+                _ => (),
+            }
+        }
+        for (i, l) in live.iter().enumerate() {
+            if !l {
+                self.arena.mapped[i] = None;
+            }
+        }
+    }
+
+    fn dyad(&mut self,
+            verb: u8,
+            left: &AST,
+            right: &AST,
+            id: otree::Id)
+            -> Result<AST, ExecError> {
+        // mapped columns take part as ordinary lists
+        let left = &self.materialize(left);
+        let right = &self.materialize(right);
+        self.profile_enter(Key::Verb(verb));
+        let r = match verb as char {
+            '+' => self.add(left, right, id),
+            '-' => self.sub(left, right, id),
+            '*' => self.prod(left, right, id),
+            '=' => self.eq(left, right, id),
+            '<' => self.less(left, right, id),
+            '>' => self.more(left, right, id),
+            _ => Err(ExecError::Undefined),
+        };
+        self.profile_exit();
+        if self.tracing() {
+            if let Ok(ref u) = r {
+                let s = format!("{} {} {}", self.line(left), verb as char, self.line(right));
+                self.trace(&s, u);
+            }
+        }
+        r
+    }
+
     fn cond(&mut self, c: &Vector<AST, ast::Id>, id: otree::Id) -> Result<AST, ExecError> {
         let (s1, s2) = handle::split(self);
         match c.as_slice(&s1.arena.ast) {
@@ -182,6 +349,7 @@ impl Interpreter {
     }
 
     fn call(&mut self, lambda: &AST, cargs: &[AST], id: otree::Id) -> Result<AST, ExecError> {
+        let name = self.callee.take();
         match lambda {
             &AST::Lambda { args: ref a, body: ref b } => {
                 let e = self.env.new_child(id);
@@ -189,14 +357,45 @@ impl Interpreter {
                     let x = try!(self.eval(&v, e));
                     let _ = self.define(*n, &x, e);
                 }
+                self.frames.push(Frame {
+                    name: name,
+                    lambda: *lambda,
+                    env: e,
+                });
+                self.stop(Stop::Call);
+                self.profile_enter(Key::Lambda(name));
+                let fname = match name {
+                    Some(n) if self.tracing() => self.arena.id_name(n),
+                    _ => "lambda".to_string(),
+                };
+                if self.tracing() {
+                    let mut s: Vec<String> = Vec::new();
+                    for n in a.iter() {
+                        if let Some((v, _)) = self.env.get(*n, e) {
+                            s.push(self.line(self.arena.ast.deref(v)));
+                        }
+                    }
+                    self.tracer.line(&format!("{}[{}]", fname, s.join(";")));
+                }
                 // if stacker::remaining_stack() <= 8013672 {
                 //     return Err(ExecError::Stack);
                 // }
-                return stacker::maybe_grow(8013672, 4 * 8013672, || {
+                let r = stacker::maybe_grow(8013672, 4 * 8013672, || {
                     let (s1, s2) = handle::split(self);
                     let u = s2.arena.ast.deref(*b);
                     s1.eval(u, e)
                 });
+                self.profile_exit();
+                if self.tracing() {
+                    if let Ok(ref u) = r {
+                        self.trace(&fname, u);
+                    }
+                }
+                if let Err(x) = r {
+                    self.stop(Stop::Error(x));
+                }
+                self.frames.pop();
+                return r;
             }
             &AST::Native { name: n } => {
                 let t = try!(self.arena.native_id_id(n).ok_or(ExecError::InvalidNativeCall));
@@ -213,7 +412,10 @@ impl Interpreter {
                     _ if t == Natives::Parse as u8 => {
                         match cargs.len() {
                             0 => Ok(*lambda),
-                            _ => self.parse_ast(&cargs[0]),
+                            _ => {
+                                let r = try!(self.eval(&cargs[0], id));
+                                self.parse_ast(&r)
+                            }
                         }
                     }
                     _ if t == Natives::Eval as u8 => {
@@ -225,9 +427,181 @@ impl Interpreter {
                             }
                         }
                     }
-                    _ if t == Natives::Debug as u8 => {
+                    _ if t == Natives::Serialize as u8 => {
+                        match cargs.len() {
+                            0 => Ok(*lambda),
+                            _ => {
+                                let r = try!(self.whole(cargs, id));
+                                let v: Vec<AST> = try!(self.serialize(&r))
+                                    .into_iter()
+                                    .map(|b| AST::Byte { value: b })
+                                    .collect();
+                                Ok(ast::list(true, &mut self.arena.ast, v))
+                            }
+                        }
+                    }
+                    _ if t == Natives::Deserialize as u8 => {
+                        match cargs.len() {
+                            0 => Ok(*lambda),
+                            _ => {
+                                let r = try!(self.whole(cargs, id));
+                                let b = try!(self.bytes(&r));
+                                self.deserialize(&b)
+                            }
+                        }
+                    }
+                    _ if t == Natives::Save as u8 || t == Natives::Load as u8 => {
+                        // save f returns f, load f the names it defined
+                        match cargs.len() {
+                            0 => Ok(*lambda),
+                            _ => {
+                                let x = try!(self.eval(&cargs[0], id));
+                                let p = try!(self.path(&x));
+                                if t == Natives::Save as u8 {
+                                    try!(self.save(&p));
+                                    return Ok(x);
+                                }
+                                let v: Vec<AST> = try!(self.load(&p))
+                                    .into_iter()
+                                    .map(|s| self.arena.intern_symbol(s))
+                                    .collect();
+                                Ok(ast::list(true, &mut self.arena.ast, v))
+                            }
+                        }
+                    }
+                    _ if t == Natives::Enlist as u8 => {
                         match cargs.len() {
                             0 => Ok(*lambda),
+                            _ => {
+                                let r = try!(self.eval(&cargs[0], id));
+                                Ok(ast::list(r.is_atom(), &mut self.arena.ast, vec![r]))
+                            }
+                        }
+                    }
+                    _ if t == Natives::ToJson as u8 => {
+                        // bytes, the text rarely fits a string
+                        match cargs.len() {
+                            0 => Ok(*lambda),
+                            _ => {
+                                let r = try!(self.whole(cargs, id));
+                                let s = try!(self.to_json(&r));
+                                let v: Vec<AST> =
+                                    s.bytes().map(|b| AST::Byte { value: b }).collect();
+                                Ok(ast::list(true, &mut self.arena.ast, v))
+                            }
+                        }
+                    }
+                    _ if t == Natives::FromJson as u8 => {
+                        match cargs.len() {
+                            0 => Ok(*lambda),
+                            _ => {
+                                let r = try!(self.whole(cargs, id));
+                                let s = match self.text(&r) {
+                                    Ok(l) => l.join("\n"),
+                                    Err(_) => {
+                                        let b = try!(self.bytes(&r));
+                                        try!(String::from_utf8(b)
+                                            .map_err(|_| ExecError::Malformed))
+                                    }
+                                };
+                                self.from_json(&s)
+                            }
+                        }
+                    }
+                    _ if t == Natives::Hopen as u8 => {
+                        // hopen 5001, hopen "host:5001:user:password"
+                        match cargs.len() {
+                            0 => Ok(*lambda),
+                            _ => {
+                                let r = try!(self.eval(&cargs[0], id));
+                                let a = match r {
+                                    AST::Int { value: p } => ipc::address(&p.to_string()),
+                                    _ => ipc::address(&try!(self.path(&r))),
+                                };
+                                let h = try!(self.handles.open(&a.0, &a.1));
+                                Ok(AST::Int { value: h })
+                            }
+                        }
+                    }
+                    _ if t == Natives::Hclose as u8 => {
+                        match cargs.len() {
+                            0 => Ok(*lambda),
+                            _ => {
+                                match try!(self.eval(&cargs[0], id)) {
+                                    AST::Int { value: h } if self.handles.close(h) => Ok(AST::Nil),
+                                    _ => Err(ExecError::Undefined),
+                                }
+                            }
+                        }
+                    }
+                    _ if t == Natives::System as u8 => {
+                        // system "ls", lines of a script run as one
+                        match cargs.len() {
+                            0 => Ok(*lambda),
+                            _ => {
+                                let r = try!(self.eval(&cargs[0], id));
+                                let cmd = try!(self.text(&r)).join("\n");
+                                let l = try!(io::system(&cmd));
+                                self.strings(&l)
+                            }
+                        }
+                    }
+                    _ if t == Natives::Dir as u8 || t == Natives::Exists as u8 ||
+                         t == Natives::Hdel as u8 ||
+                         t == Natives::Rename as u8 ||
+                         t == Natives::Hcount as u8 ||
+                         t == Natives::Mtime as u8 ||
+                         t == Natives::Getenv as u8 ||
+                         t == Natives::Setenv as u8 => {
+                        match cargs.len() {
+                            0 => Ok(*lambda),
+                            _ => self.files(t, cargs, id),
+                        }
+                    }
+                    _ if t == Natives::Splay as u8 => {
+                        // splay[dir;t] writes t as a directory of columns
+                        match cargs.len() {
+                            0 => Ok(*lambda),
+                            2 => {
+                                let x = try!(self.eval(&cargs[0], id));
+                                let d = try!(self.path(&x));
+                                let y = try!(self.eval(&cargs[1], id));
+                                try!(splay::write(&d, &y, &mut self.arena));
+                                Ok(x)
+                            }
+                            _ => Err(ExecError::Rank),
+                        }
+                    }
+                    _ if t == Natives::Get as u8 => {
+                        // get dir, get[root;`table] for a date partitioned one
+                        match cargs.len() {
+                            0 => Ok(*lambda),
+                            1 => {
+                                let x = try!(self.eval(&cargs[0], id));
+                                let d = try!(self.path(&x));
+                                splay::read(&d, &mut self.arena)
+                            }
+                            2 => {
+                                let x = try!(self.eval(&cargs[0], id));
+                                let d = try!(self.path(&x));
+                                let y = try!(self.eval(&cargs[1], id));
+                                let t = try!(self.path(&y));
+                                let p = try!(splay::partitioned(&d, &t));
+                                self.arena.partitioned.push(p);
+                                Ok(AST::Partitioned { id: (self.arena.partitioned.len() - 1) as u32 })
+                            }
+                            _ => Err(ExecError::Rank),
+                        }
+                    }
+                    _ if t == Natives::Debug as u8 => {
+                        match cargs {
+                            &[] => Ok(*lambda),
+                            &[AST::Name { value: n }] => {
+                                // debug f: suspend on every call of f
+                                self.debugger.breakpoints.retain(|x| *x != n);
+                                self.debugger.breakpoints.push(n);
+                                Ok(AST::Nil)
+                            }
                             _ => {
                                 // Must be way to find Id by &AST to avoid duplication of type
                                 // when we want to debug.
@@ -239,11 +613,499 @@ impl Interpreter {
                     _ => Err(ExecError::InvalidNativeCall),
                 };
             }
+            &AST::Ioverb { fd: f } => return self.ioverb(f, cargs, id),
+            &AST::Int { value: h } if self.handles.contains(h.abs()) && !cargs.is_empty() => {
+                // h x waits for the remote result, (0-h) x sends without waiting
+                let x = try!(self.eval(&cargs[0], id));
+                return self.remote(h, &x);
+            }
+            &AST::Table { columns: ref c, values: ref v } => {
+                // t.price, t`price or t 0 for a row as a dict
+                let key = match cargs.get(0) {
+                    Some(&AST::Name { value: n }) => {
+                        let s = self.arena.id_name(n);
+                        let column = self.arena.symbols.get(&s).map(|x| *x);
+                        match column {
+                            Some(x) => AST::Symbol { value: x },
+                            None => try!(self.eval(&cargs[0], id)),
+                        }
+                    }
+                    Some(x) => try!(self.eval(x, id)),
+                    None => return Ok(*lambda),
+                };
+                return self.table_index(c, v, &key);
+            }
+            &AST::Partitioned { id: p } if !cargs.is_empty() => {
+                let key = match cargs[0] {
+                    AST::Name { value: n } => {
+                        let s = self.arena.id_name(n);
+                        self.arena.intern_symbol(s)
+                    }
+                    _ => try!(self.whole(cargs, id)),
+                };
+                return self.partitioned_index(p as usize, &key);
+            }
+            &AST::Mapped { id: _ } if !cargs.is_empty() => {
+                // reads only the elements asked for
+                let x = try!(self.whole(cargs, id));
+                return match x {
+                    AST::Int { value: i } if i >= 0 => {
+                        ast::item(lambda, i as usize, &self.arena).ok_or(ExecError::Length)
+                    }
+                    _ => {
+                        let mut r: Vec<AST> = Vec::new();
+                        for i in try!(self.ints(&x)) {
+                            let v = ast::item(lambda, i as usize, &self.arena);
+                            r.push(try!(v.filter(|_| i >= 0).ok_or(ExecError::Length)));
+                        }
+                        Ok(ast::list(true, &mut self.arena.ast, r))
+                    }
+                };
+            }
+            &AST::Temporal { kind: k, value: v } => {
+                // component extraction: d.year, t.hh ...
+                if let Some(&AST::Name { value: n }) = cargs.get(0) {
+                    let c = self.arena.id_name(n);
+                    return temporal::component(k, v, &c).ok_or(ExecError::Type);
+                }
+            }
             _ => (),
         }
         Err(ExecError::Call)
     }
 
+    // The argument of a function of one value. A vector literal applied with
+    // @ arrives as its items, which are put back into one list.
+    fn whole(&mut self, cargs: &[AST], id: otree::Id) -> Result<AST, ExecError> {
+        if cargs.len() == 1 {
+            return self.eval(&cargs[0], id);
+        }
+        let mut v: Vec<AST> = Vec::new();
+        for a in cargs {
+            v.push(try!(self.eval(a, id)));
+        }
+        Ok(ast::list(true, &mut self.arena.ast, v))
+    }
+
+    fn target(&self, ast: &AST) -> Result<Target, ExecError> {
+        match *ast {
+            AST::Int { value: v } => Ok(Target::Fd(v)),
+            _ => self.path(ast).map(Target::Path),
+        }
+    }
+
+    // A string or a list of strings as lines of text.
+    pub fn text(&self, ast: &AST) -> Result<Vec<String>, ExecError> {
+        if let Some(s) = self.arena.string_of(ast) {
+            return Ok(vec![s]);
+        }
+        match *ast {
+            AST::List { curry: _, values: ref v } => {
+                let mut r: Vec<String> = Vec::new();
+                for x in v.iter(&self.arena.ast) {
+                    r.push(try!(self.arena.string_of(x).ok_or(ExecError::Type)));
+                }
+                Ok(r)
+            }
+            _ => Err(ExecError::Type),
+        }
+    }
+
+    // A byte or a list of bytes.
+    pub fn bytes(&self, ast: &AST) -> Result<Vec<u8>, ExecError> {
+        match *ast {
+            AST::Byte { value: b } => Ok(vec![b]),
+            AST::List { curry: _, values: ref v } => {
+                let mut r: Vec<u8> = Vec::new();
+                for x in v.iter(&self.arena.ast) {
+                    match *x {
+                        AST::Byte { value: b } => r.push(b),
+                        _ => return Err(ExecError::Type),
+                    }
+                }
+                Ok(r)
+            }
+            _ => Err(ExecError::Type),
+        }
+    }
+
+    fn ints(&self, ast: &AST) -> Result<Vec<i64>, ExecError> {
+        match *ast {
+            AST::Int { value: i } => Ok(vec![i]),
+            AST::List { curry: _, values: ref v } => {
+                let mut r: Vec<i64> = Vec::new();
+                for x in v.iter(&self.arena.ast) {
+                    match *x {
+                        AST::Int { value: i } => r.push(i),
+                        _ => return Err(ExecError::Type),
+                    }
+                }
+                Ok(r)
+            }
+            _ => Err(ExecError::Type),
+        }
+    }
+
+    // Record spec of 1:, a pair of field types and widths: ("ijf";4 8 8).
+    fn record_spec(&self, ast: &AST) -> Option<Result<Vec<io::Field>, ExecError>> {
+        match *ast {
+            AST::List { curry: false, values: ref v } if v.len() == 2 => {
+                match (*v.get(0, &self.arena.ast), *v.get(1, &self.arena.ast)) {
+                    (AST::String { value: s }, ref w) => {
+                        Some(self.ints(w).and_then(|w| io::fields(&s.to_string(), &w)))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // Spec of delimited text for 0:, field types and a delimiter which is
+    // enlisted when the first line is a header: ("IFS";enlist ",").
+    fn text_spec(&self, ast: &AST) -> Option<(String, char, bool)> {
+        let delim = |x: &AST| match *x {
+            AST::String { value: s } => s.to_string().chars().nth(0),
+            _ => None,
+        };
+        match *ast {
+            AST::List { curry: false, values: ref v } if v.len() == 2 => {
+                let t = match *v.get(0, &self.arena.ast) {
+                    AST::String { value: s } => s.to_string(),
+                    _ => return None,
+                };
+                match *v.get(1, &self.arena.ast) {
+                    AST::List { curry: _, values: ref d } if d.len() == 1 => {
+                        delim(d.get(0, &self.arena.ast)).map(|c| (t, c, true))
+                    }
+                    ref d => delim(d).map(|c| (t, c, false)),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn column(&mut self, c: Column) -> Result<AST, ExecError> {
+        let v: Vec<AST> = match c {
+            Column::Bool(v) => v.into_iter().map(|x| AST::Bool { value: x }).collect(),
+            Column::Byte(v) => v.into_iter().map(|x| AST::Byte { value: x }).collect(),
+            Column::Int(v) => v.into_iter().map(|x| AST::Int { value: x }).collect(),
+            Column::Float(v) => v.into_iter().map(|x| AST::Float { value: x }).collect(),
+            Column::Symbol(v) => v.into_iter().map(|x| self.arena.intern_symbol(x)).collect(),
+            Column::Chars(v) => {
+                let mut r: Vec<AST> = Vec::new();
+                for x in v {
+                    r.push(self.arena.string(x));
+                }
+                return Ok(ast::list(false, &mut self.arena.ast, r));
+            }
+            Column::Temporal(k, v) => {
+                v.into_iter()
+                    .map(|x| {
+                        AST::Temporal {
+                            kind: k,
+                            value: x,
+                        }
+                    })
+                    .collect()
+            }
+        };
+        Ok(ast::list(true, &mut self.arena.ast, v))
+    }
+
+    // 0:x reads the lines of file or handle x, x 0:y writes the lines y and
+    // (types;delimiter)0:x loads delimited text into columns or a table.
+    // 1:x reads the bytes of x, x 1:y writes the bytes y and (types;widths)1:x
+    // decodes x as fixed width records into one list per field.
+    fn ioverb(&mut self, fd: u8, cargs: &[AST], id: otree::Id) -> Result<AST, ExecError> {
+        match (fd, cargs.len()) {
+            (_, 0) => Ok(AST::Ioverb { fd: fd }),
+            (0, 1) => {
+                let x = try!(self.eval(&cargs[0], id));
+                let t = try!(self.target(&x));
+                let l = try!(io::read_text(&t));
+                self.strings(&l)
+            }
+            (0, 2) => {
+                let x = try!(self.eval(&cargs[0], id));
+                let y = try!(self.eval(&cargs[1], id));
+                if let Some((types, delim, header)) = self.text_spec(&x) {
+                    let t = try!(self.target(&y));
+                    let text = try!(io::read_text(&t)).join("\n");
+                    let (names, cols) = try!(io::delimited(&text, &types, delim, header));
+                    let mut r: Vec<AST> = Vec::new();
+                    for c in cols {
+                        r.push(try!(self.column(c)));
+                    }
+                    if !header {
+                        return Ok(ast::list(false, &mut self.arena.ast, r));
+                    }
+                    let n: Vec<AST> =
+                        names.into_iter().map(|s| self.arena.intern_symbol(s)).collect();
+                    return Ok(ast::table(&mut self.arena.ast, n, r));
+                }
+                let t = try!(self.target(&x));
+                let l = try!(self.text(&y));
+                try!(io::write_text(&t, &l));
+                Ok(x)
+            }
+            (1, 1) => {
+                let x = try!(self.eval(&cargs[0], id));
+                let t = try!(self.target(&x));
+                let b = try!(io::read_bytes(&t));
+                let v: Vec<AST> = b.into_iter().map(|c| AST::Byte { value: c }).collect();
+                Ok(ast::list(true, &mut self.arena.ast, v))
+            }
+            (1, 2) => {
+                let x = try!(self.eval(&cargs[0], id));
+                let y = try!(self.eval(&cargs[1], id));
+                if let Some(spec) = self.record_spec(&x) {
+                    let f = try!(spec);
+                    let t = try!(self.target(&y));
+                    let b = try!(io::read_bytes(&t));
+                    let mut r: Vec<AST> = Vec::new();
+                    for c in try!(io::records(&b, &f)) {
+                        r.push(try!(self.column(c)));
+                    }
+                    return Ok(ast::list(false, &mut self.arena.ast, r));
+                }
+                let t = try!(self.target(&x));
+                let b = try!(self.bytes(&y));
+                try!(io::write_bytes(&t, &b));
+                Ok(x)
+            }
+            (_, 1) | (_, 2) => Err(ExecError::NotImplemented),
+            _ => Err(ExecError::Rank),
+        }
+    }
+
+    fn remote(&mut self, h: i64, x: &AST) -> Result<AST, ExecError> {
+        let kind = if h < 0 { Kind::Async } else { Kind::Sync };
+        let b = try!(self.serialize(x));
+        let m = try!(self.handles.request(h.abs(), kind, &b));
+        match kind {
+            Kind::Sync if m.error => {
+                let s = String::from_utf8_lossy(&m.payload).into_owned();
+                Err(ExecError::from_name(&s).unwrap_or(ExecError::RuntimeError))
+            }
+            Kind::Sync => self.deserialize_value(&m.payload),
+            _ => Ok(AST::Nil),
+        }
+    }
+
+    // A file or directory name as a string or a symbol.
+    fn path(&self, ast: &AST) -> Result<String, ExecError> {
+        match *ast {
+            AST::Symbol { value: v } => Ok(self.arena.id_symbol(v)),
+            _ => self.arena.string_of(ast).ok_or(ExecError::Type),
+        }
+    }
+
+    // Filesystem and environment natives:
+    //   dir p            sorted entry names as symbols
+    //   exists p         1b if there is a file or directory p
+    //   hdel p           deletes a file or an empty directory, returns p
+    //   rename[p;q]      moves p to q, returns q
+    //   hcount p         size in bytes
+    //   mtime p          modification time as a timestamp
+    //   getenv n         value of variable n, "" if unset
+    //   setenv[n;v]      sets variable n to v, returns v
+    fn files(&mut self, t: u8, cargs: &[AST], id: otree::Id) -> Result<AST, ExecError> {
+        let x = try!(self.eval(&cargs[0], id));
+        let p = try!(self.path(&x));
+        let two = t == Natives::Rename as u8 || t == Natives::Setenv as u8;
+        if two != (cargs.len() == 2) {
+            return Err(ExecError::Rank);
+        }
+        match t {
+            _ if t == Natives::Dir as u8 => {
+                let v: Vec<AST> =
+                    try!(io::dir(&p)).into_iter().map(|s| self.arena.intern_symbol(s)).collect();
+                Ok(ast::list(true, &mut self.arena.ast, v))
+            }
+            _ if t == Natives::Exists as u8 => Ok(AST::Bool { value: io::exists(&p) }),
+            _ if t == Natives::Hdel as u8 => {
+                try!(io::delete(&p));
+                Ok(x)
+            }
+            _ if t == Natives::Rename as u8 => {
+                let y = try!(self.eval(&cargs[1], id));
+                let q = try!(self.path(&y));
+                try!(io::rename(&p, &q));
+                Ok(y)
+            }
+            _ if t == Natives::Hcount as u8 => Ok(AST::Int { value: try!(io::size(&p)) }),
+            _ if t == Natives::Mtime as u8 => {
+                Ok(AST::Temporal {
+                    kind: temporal::Temporal::Timestamp,
+                    value: try!(io::mtime(&p)),
+                })
+            }
+            _ if t == Natives::Getenv as u8 => {
+                let v = env::var(&p).unwrap_or(String::new());
+                Ok(self.arena.string(v))
+            }
+            _ => {
+                let y = try!(self.eval(&cargs[1], id));
+                let v = try!(self.text(&y)).join("\n");
+                if p.is_empty() || p.contains('=') || p.contains('\0') || v.contains('\0') {
+                    return Err(ExecError::Type);
+                }
+                env::set_var(&p, &v);
+                Ok(y)
+            }
+        }
+    }
+
+    fn partition(&mut self, p: usize, i: usize) -> Result<AST, ExecError> {
+        if let Some(t) = self.arena.partitioned[p].loaded[i] {
+            return Ok(t);
+        }
+        let (dir, date) = {
+            let x = &self.arena.partitioned[p];
+            let d = Path::new(&x.root).join(&x.dirs[i]).join(&x.table);
+            (d.to_string_lossy().into_owned(), x.dates[i])
+        };
+        let t = try!(splay::partition(&dir, date, &mut self.arena));
+        self.arena.partitioned[p].loaded[i] = Some(t);
+        Ok(t)
+    }
+
+    // Rows of partitions in one table, columns as in the first.
+    fn union(&mut self, tables: &[AST]) -> Result<AST, ExecError> {
+        let columns: Vec<AST> = match tables.first() {
+            Some(&AST::Table { columns: ref c, .. }) => c.as_slice(&self.arena.ast).to_vec(),
+            _ => return Err(ExecError::Type),
+        };
+        let mut values: Vec<AST> = Vec::new();
+        for c in &columns {
+            let mut v: Vec<AST> = Vec::new();
+            for t in tables {
+                if let AST::Table { columns: ref tc, values: ref tv } = *t {
+                    let x = try!(self.table_index(tc, tv, c));
+                    v.extend(try!(ast::items(&x, &self.arena).ok_or(ExecError::Type)));
+                }
+            }
+            values.push(ast::list(true, &mut self.arena.ast, v));
+        }
+        Ok(ast::table(&mut self.arena.ast, columns, values))
+    }
+
+    // p 2024.01.31 is one partition and a list of dates their rows, only
+    // those partitions are opened. A column or a row reads them all.
+    fn partitioned_index(&mut self, p: usize, key: &AST) -> Result<AST, ExecError> {
+        let dates = self.arena.partitioned[p].dates.clone();
+        match *key {
+            AST::Temporal { kind: temporal::Temporal::Date, value: d } => {
+                match dates.iter().position(|x| *x == d) {
+                    Some(i) => self.partition(p, i),
+                    None => {
+                        let t = try!(self.partition(p, 0));
+                        self.union(&[t]).and_then(|u| self.empty(&u))
+                    }
+                }
+            }
+            AST::List { .. } => {
+                let wanted = try!(ast::items(key, &self.arena).ok_or(ExecError::Type));
+                let mut tables: Vec<AST> = Vec::new();
+                for (i, d) in dates.iter().enumerate() {
+                    let hit = wanted.iter().any(|x| match *x {
+                        AST::Temporal { kind: temporal::Temporal::Date, value: v } => v == *d,
+                        _ => false,
+                    });
+                    if hit {
+                        tables.push(try!(self.partition(p, i)));
+                    }
+                }
+                if tables.is_empty() {
+                    let t = try!(self.partition(p, 0));
+                    return self.union(&[t]).and_then(|u| self.empty(&u));
+                }
+                self.union(&tables)
+            }
+            AST::Symbol { .. } => {
+                let mut v: Vec<AST> = Vec::new();
+                for i in 0..dates.len() {
+                    if let AST::Table { columns: c, values: x } = try!(self.partition(p, i)) {
+                        let col = try!(self.table_index(&c, &x, key));
+                        v.extend(try!(ast::items(&col, &self.arena).ok_or(ExecError::Type)));
+                    }
+                }
+                Ok(ast::list(true, &mut self.arena.ast, v))
+            }
+            AST::Int { value: mut r } if r >= 0 => {
+                for i in 0..dates.len() {
+                    if let AST::Table { columns: c, values: x } = try!(self.partition(p, i)) {
+                        let n = x.as_slice(&self.arena.ast)
+                            .first()
+                            .and_then(|v| ast::count(v, &self.arena))
+                            .unwrap_or(0) as i64;
+                        if r < n {
+                            return self.table_index(&c, &x, &AST::Int { value: r });
+                        }
+                        r -= n;
+                    }
+                }
+                Err(ExecError::Length)
+            }
+            _ => Err(ExecError::Type),
+        }
+    }
+
+    // Table t without rows.
+    fn empty(&mut self, t: &AST) -> Result<AST, ExecError> {
+        match *t {
+            AST::Table { columns: ref c, values: ref v } => {
+                let columns = c.as_slice(&self.arena.ast).to_vec();
+                let values = (0..v.len())
+                    .map(|_| ast::list(true, &mut self.arena.ast, Vec::new()))
+                    .collect();
+                Ok(ast::table(&mut self.arena.ast, columns, values))
+            }
+            _ => Err(ExecError::Type),
+        }
+    }
+
+    fn table_index(&mut self,
+                   columns: &Vector<AST, ast::Id>,
+                   values: &Vector<AST, ast::Id>,
+                   key: &AST)
+                   -> Result<AST, ExecError> {
+        match *key {
+            AST::Symbol { value: s } => {
+                for (c, v) in columns.iter(&self.arena.ast).zip(values.iter(&self.arena.ast)) {
+                    if let AST::Symbol { value: x } = *c {
+                        if x == s {
+                            return Ok(*v);
+                        }
+                    }
+                }
+                Err(ExecError::Undefined)
+            }
+            AST::Int { value: i } => {
+                let cols: Vec<(AST, AST)> = columns.iter(&self.arena.ast)
+                    .zip(values.iter(&self.arena.ast))
+                    .map(|(c, v)| (*c, *v))
+                    .collect();
+                let mut keys: Vec<AST> = Vec::new();
+                let mut row: Vec<AST> = Vec::new();
+                for (c, v) in cols {
+                    let s = match c {
+                        AST::Symbol { value: x } => self.arena.id_symbol(x),
+                        _ => return Err(ExecError::Type),
+                    };
+                    match ast::item(&v, i as usize, &self.arena) {
+                        Some(x) if i >= 0 => row.push(x),
+                        _ => return Err(ExecError::Length),
+                    }
+                    keys.push(self.arena.intern_name(s));
+                }
+                Ok(ast::dict(&mut self.arena.ast, keys, row))
+            }
+            _ => Err(ExecError::Type),
+        }
+    }
+
     fn apply(&mut self, lambda: &AST, args: &[AST], id: otree::Id) -> Result<AST, ExecError> {
         self.call(lambda, args, id)
     }
@@ -251,7 +1113,7 @@ impl Interpreter {
     fn define(&mut self, key: u16, value: &AST, id: otree::Id) -> Result<ast::Id, ExecError> {
         let v = try!(self.eval(value, id));
         let u = self.store(v);
-        self.env.define(key, u);
+        self.env.define(key, u, id);
         Ok(u)
     }
 
@@ -271,51 +1133,351 @@ impl Interpreter {
         self.parser.parse(b, &mut self.arena)
     }
 
+    // Names bound in the global environment, natives excluded.
+    pub fn names(&self) -> Vec<String> {
+        let id = self.env.root();
+        let mut r: Vec<String> = self.arena
+            .names
+            .iter()
+            .filter(|&(_, n)| self.env.get(*n, id).is_some())
+            .map(|(k, _)| k.clone())
+            .collect();
+        r.sort();
+        r
+    }
+
+    // Arena cells in use and environment (nodes, entries).
+    pub fn memory(&self) -> (usize, (usize, usize)) {
+        (self.arena.ast.len(), self.env.len())
+    }
+
+    pub fn set(&mut self, name: &str, value: AST) {
+        let key = self.arena.intern_name_id(name.to_string());
+        let u = self.store(value);
+        let id = self.env.root();
+        self.env.define(key, u, id);
+    }
+
+    pub fn strings(&mut self, v: &[String]) -> Result<AST, ExecError> {
+        let mut r: Vec<AST> = Vec::new();
+        for s in v {
+            r.push(self.arena.string(s.clone()));
+        }
+        Ok(ast::list(false, &mut self.arena.ast, r))
+    }
+
+    // Binary form of a value, see exec::serial.
+    pub fn serialize(&self, ast: &AST) -> Result<Vec<u8>, ExecError> {
+        serial::encode(ast, &self.arena)
+    }
+
+    pub fn deserialize(&mut self, b: &[u8]) -> Result<AST, ExecError> {
+        serial::decode(b, &mut self.arena)
+    }
+
+    // Values from other processes, expressions are refused so that nothing
+    // runs before the handlers see it.
+    pub fn deserialize_value(&mut self, b: &[u8]) -> Result<AST, ExecError> {
+        serial::decode_value(b, &mut self.arena)
+    }
+
+    // Writes the globals to path as a dict of their names to their values in
+    // the binary form of exec::serial. Partitioned tables are views of files
+    // and left out. Returns the names written.
+    pub fn save(&mut self, path: &str) -> Result<Vec<String>, ExecError> {
+        let id = self.env.root();
+        let mut names: Vec<String> = Vec::new();
+        let mut keys: Vec<AST> = Vec::new();
+        let mut values: Vec<AST> = Vec::new();
+        for name in self.names() {
+            let n = self.arena.name_id(&name);
+            let v = *try!(self.get(n, id));
+            if let AST::Partitioned { .. } = v {
+                continue;
+            }
+            keys.push(self.arena.intern_symbol(name.clone()));
+            values.push(v);
+            names.push(name);
+        }
+        let d = ast::dict(&mut self.arena.ast, keys, values);
+        let b = try!(self.serialize(&d));
+        try!(io::write_bytes(&Target::Path(path.to_string()), &b));
+        Ok(names)
+    }
+
+    // Defines the globals of a file written by save, returns their names.
+    pub fn load(&mut self, path: &str) -> Result<Vec<String>, ExecError> {
+        let b = try!(io::read_bytes(&Target::Path(path.to_string())));
+        let (k, v) = match try!(self.deserialize(&b)) {
+            AST::Dict { keys: k, values: v } => {
+                (k.as_slice(&self.arena.ast).to_vec(), v.as_slice(&self.arena.ast).to_vec())
+            }
+            _ => return Err(ExecError::Malformed),
+        };
+        let mut names: Vec<String> = Vec::new();
+        for key in &k {
+            match *key {
+                AST::Symbol { value: s } => names.push(self.arena.id_symbol(s)),
+                _ => return Err(ExecError::Malformed),
+            }
+        }
+        if names.iter().any(|s| s.is_empty()) {
+            return Err(ExecError::Malformed);
+        }
+        for (name, value) in names.iter().zip(v.into_iter()) {
+            self.set(name, value);
+        }
+        Ok(names)
+    }
+
+    // JSON text of a value, see exec::json.
+    pub fn to_json(&self, ast: &AST) -> Result<String, ExecError> {
+        json::encode(ast, &self.arena)
+    }
+
+    pub fn from_json(&mut self, s: &str) -> Result<AST, ExecError> {
+        json::decode(s, &mut self.arena)
+    }
+
+    // Evaluates a message received from another process: a string or lines of
+    // text are run as an expression, (f;x;y) applies f, a function or the
+    // symbol of a global, to the rest, any other value is its own result.
+    pub fn execute(&mut self, msg: &AST) -> Result<AST, ExecError> {
+        if let AST::List { curry: false, values: v } = *msg {
+            let items: Vec<AST> = v.iter(&self.arena.ast).map(|x| *x).collect();
+            let f = match items.first() {
+                Some(&AST::Symbol { value: s }) => {
+                    let name = self.arena.id_symbol(s);
+                    let n = self.arena.intern_name_id(name);
+                    let id = self.env.root();
+                    Some(*try!(self.get(n, id)))
+                }
+                Some(&AST::Lambda { .. }) |
+                Some(&AST::Native { .. }) |
+                Some(&AST::Verb { .. }) => Some(items[0]),
+                _ => None,
+            };
+            if let Some(f) = f {
+                let id = self.env.root();
+                return self.call(&f, &items[1..], id);
+            }
+        }
+        match self.text(msg) {
+            Ok(l) => {
+                let n = try!(self.parser
+                    .parse_str(&l.join(";"), &mut self.arena)
+                    .map_err(|_| ExecError::Malformed));
+                self.run(&n)
+            }
+            Err(_) => Ok(*msg),
+        }
+    }
+
+    // Calls the global function name with args, None when it is not defined.
+    pub fn hook(&mut self, name: &str, args: &[AST]) -> Option<Result<AST, ExecError>> {
+        let n = self.arena.intern_name_id(name.to_string());
+        let id = self.env.root();
+        let f = match self.env.get(n, id) {
+            Some((v, _)) => *self.arena.ast.deref(v),
+            None => return None,
+        };
+        self.callee = Some(n);
+        Some(self.call(&f, args, id))
+    }
+
+    // True for 1b, a non zero integer or a list of one such, 1b is parsed as ,1.
+    pub fn truth(&self, ast: &AST) -> bool {
+        match *ast {
+            AST::Bool { value: b } => b,
+            AST::Int { value: v } => v != 0,
+            AST::List { curry: _, values: ref v } if v.len() == 1 => {
+                self.truth(v.get(0, &self.arena.ast))
+            }
+            _ => false,
+        }
+    }
+
+    pub fn symbol(&mut self, s: &str) -> AST {
+        self.arena.intern_symbol(s.to_string())
+    }
+
+    pub fn string(&mut self, s: &str) -> AST {
+        self.arena.string(s.to_string())
+    }
+
     pub fn run(&mut self, node: &AST) -> Result<AST, ExecError> {
-        let id = self.env.last();
+        let id = self.env.root();
+        if self.frames.is_empty() {
+            self.debugger.reset();
+            self.release();
+        }
         self.eval(node, id)
     }
 
+    // Evaluates the text s in the global environment for a Rust host, the
+    // result is owned and outlives later evaluations.
+    pub fn eval_str(&mut self, s: &str) -> Result<Value, value::Error> {
+        let n = try!(self.parse(s.as_bytes()));
+        let r = try!(self.run(&n));
+        Ok(value::from_ast(&r, &mut self.arena))
+    }
+
+    // Calls a function returned by eval_str with args.
+    pub fn call_value(&mut self, f: &Function, args: &[Value]) -> Result<Value, value::Error> {
+        let f = try!(value::to_ast(&Value::Function(f.clone()), &mut self.arena));
+        let mut v: Vec<AST> = Vec::new();
+        for a in args {
+            v.push(try!(value::to_ast(a, &mut self.arena)));
+        }
+        let id = self.env.root();
+        let r = try!(self.call(&f, &v, id));
+        Ok(value::from_ast(&r, &mut self.arena))
+    }
+
+    // Evaluates node in the environment of a frame from backtrace.
+    pub fn run_at(&mut self, node: &AST, frame: usize) -> Result<AST, ExecError> {
+        let id = try!(self.frames.get(frame).map(|f| f.env).ok_or(ExecError::Stack));
+        self.eval(node, id)
+    }
+
+    pub fn backtrace(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn set_hook(&mut self, hook: Option<Hook>) {
+        self.debugger.hook = hook;
+    }
+
+    pub fn set_breakpoint(&mut self, name: &str, on: bool) {
+        let n = self.arena.intern_name_id(name.to_string());
+        self.debugger.breakpoints.retain(|x| *x != n);
+        if on {
+            self.debugger.breakpoints.push(n);
+        }
+    }
+
+    pub fn breakpoints(&self) -> Vec<String> {
+        self.debugger.breakpoints.iter().map(|n| self.arena.id_name(*n)).collect()
+    }
+
+    pub fn set_suspend_on_error(&mut self, on: bool) {
+        self.debugger.on_error = on;
+    }
+
+    pub fn name(&self, id: u16) -> String {
+        self.arena.id_name(id)
+    }
+
+    pub fn set_profile(&mut self, on: bool) {
+        if on && !self.profiler.enabled {
+            self.profiler.reset();
+        }
+        self.profiler.enabled = on;
+    }
+
+    // Profile entries sorted by exclusive time, lambdas by the name they were
+    // called through and verbs by their symbol.
+    pub fn profile(&self) -> Vec<(String, Entry)> {
+        self.profiler
+            .report()
+            .into_iter()
+            .map(|(k, e)| {
+                let n = match k {
+                    Key::Lambda(Some(n)) => self.arena.id_name(n),
+                    Key::Lambda(None) => "lambda".to_string(),
+                    Key::Verb(v) => (v as char).to_string(),
+                };
+                (n, e)
+            })
+            .collect()
+    }
+
+    #[inline]
+    fn profile_enter(&mut self, key: Key) {
+        if self.profiler.enabled {
+            self.profiler.enter(key, self.arena.ast.len());
+        }
+    }
+
+    #[inline]
+    fn profile_exit(&mut self) {
+        if self.profiler.enabled {
+            self.profiler.exit(self.arena.ast.len());
+        }
+    }
+
+    // Trace everything, or only inside the functions of the filter. The depth
+    // is left alone, evaluations in progress still undo their indentation.
+    pub fn set_trace(&mut self, on: bool, names: &[&str]) {
+        let mut filter: Vec<u16> = Vec::new();
+        for n in names {
+            filter.push(self.arena.intern_name_id(n.to_string()));
+        }
+        self.tracer.enabled = on;
+        self.tracer.filter = filter;
+    }
+
+    fn tracing(&self) -> bool {
+        self.tracer.enabled &&
+        (self.tracer.filter.is_empty() ||
+         self.frames.iter().any(|f| f.name.map_or(false, |n| self.tracer.filter.contains(&n))))
+    }
+
+    // First line of the formatted value.
+    fn line(&self, ast: &AST) -> String {
+        self.format(ast).lines().nth(0).unwrap_or("").to_string()
+    }
+
+    fn trace(&self, what: &str, result: &AST) {
+        self.tracer.line(&format!("{} -> {}", what, self.line(result)));
+    }
+
+    fn stop(&mut self, stop: Stop) {
+        let depth = self.frames.len();
+        if self.debugger.hit(&stop, self.frames.last(), depth) {
+            let hook = self.debugger.hook.unwrap();
+            self.debugger.step = Step::Run;
+            let step = hook(self, stop);
+            self.debugger.step = step;
+        }
+    }
+
     fn eval(&mut self, node: &AST, id: otree::Id) -> Result<AST, ExecError> {
+        if !self.tracer.enabled {
+            return self.reduce(node, id);
+        }
+        self.tracer.depth += 1;
+        let r = self.reduce(node, id);
+        self.tracer.depth -= 1;
+        r
+    }
+
+    fn reduce(&mut self, node: &AST, id: otree::Id) -> Result<AST, ExecError> {
         match *node {
             AST::Verb { kind: k, args: a } => {
                 if a.as_slice(&self.arena.ast).len() == 0 {
                     return Ok(AST::Verb { kind: k, args: a });
                 }
                 match k as char {
-                    '+' => {
+                    '+' | '-' | '*' | '=' | '<' | '>' => {
                         let h = handle::into_raw(self);
                         let arg = a.as_slice(&handle::from_raw(h).arena.ast);
                         let x = try!(handle::from_raw(h).eval(&arg[0], id));
                         let y = try!(handle::from_raw(h).eval(&arg[1], id));
-                        return handle::from_raw(h).add(&x, &y, id);
-                    }
-                    '-' => {
-                        let h = handle::into_raw(self);
-                        let arg = a.as_slice(&handle::from_raw(h).arena.ast);
-                        let x = try!(handle::from_raw(h).eval(&arg[0], id));
-                        let y = try!(handle::from_raw(h).eval(&arg[1], id));
-                        return handle::from_raw(h).sub(&x, &y, id);
-                    }
-                    '*' => {
-                        let h = handle::into_raw(self);
-                        let arg = a.as_slice(&handle::from_raw(h).arena.ast);
-                        let x = try!(handle::from_raw(h).eval(&arg[0], id));
-                        let y = try!(handle::from_raw(h).eval(&arg[1], id));
-                        return handle::from_raw(h).prod(&x, &y, id);
-                    }
-                    '=' => {
-                        let h = handle::into_raw(self);
-                        let arg = a.as_slice(&handle::from_raw(h).arena.ast);
-                        let x = try!(handle::from_raw(h).eval(&arg[0], id));
-                        let y = try!(handle::from_raw(h).eval(&arg[1], id));
-                        return handle::from_raw(h).eq(&x, &y, id);
+                        return handle::from_raw(h).dyad(k, &x, &y, id);
                     }
                     '.' => {
                         let h = handle::into_raw(self);
                         let x = try!(handle::from_raw(h)
                             .eval(a.get(0, &handle::from_raw(h).arena.ast), id));
+                        let f = *a.get(0, &handle::from_raw(h).arena.ast);
+                        handle::from_raw(h).callee = match f {
+                            AST::Name { value: n } => Some(n),
+                            _ => None,
+                        };
+                        // f[x;y] passes its arguments as a sequence, f . x the items of x
                         match a.get(1, &handle::from_raw(h).arena.ast) {
+                            &AST::Sequence { values: ref v } |
                             &AST::List { curry: true, values: ref v } => {
                                 return handle::from_raw(h)
                                     .call(&x, v.as_slice(&mut handle::from_raw(h).arena.ast), id);
@@ -332,6 +1494,11 @@ impl Interpreter {
                         let h = handle::into_raw(self);
                         let x = try!(handle::from_raw(h)
                             .eval(a.get(0, &handle::from_raw(h).arena.ast), id));
+                        let f = *a.get(0, &handle::from_raw(h).arena.ast);
+                        handle::from_raw(h).callee = match f {
+                            AST::Name { value: n } => Some(n),
+                            _ => None,
+                        };
                         match a.get(1, &handle::from_raw(h).arena.ast) {
                             &AST::List { curry: true, values: ref v } => {
                                 return handle::from_raw(h)
@@ -375,11 +1542,21 @@ impl Interpreter {
                 let a = handle::from_raw(h).arena.ast.deref(v);
                 let u = try!(handle::from_raw(h).eval(a, id));
                 let _ = try!(handle::from_raw(h).define(n, &u, id));
+                if handle::from_raw(h).tracing() {
+                    let s = format!("{}:{}",
+                                    handle::from_raw(h).arena.id_name(n),
+                                    handle::from_raw(h).line(&u));
+                    handle::from_raw(h).tracer.line(&s);
+                }
                 return Ok(u);
             }
             AST::Name { value: n } => {
-                let u = try!(self.get(n, id));
-                return Ok(*u);
+                let u = *try!(self.get(n, id));
+                if self.tracing() {
+                    let s = self.arena.id_name(n);
+                    self.trace(&s, &u);
+                }
+                return Ok(u);
             }
             AST::Int { value: v } => return Ok(AST::Int { value: v }),
             AST::List { curry: c, values: v } => {
@@ -395,6 +1572,7 @@ impl Interpreter {
             AST::Sequence { values: v } => {
                 let (s1, s2) = handle::split(self);
                 for u in v.as_slice_mut(&mut s1.arena.ast) {
+                    s2.stop(Stop::Statement(*u));
                     *u = try!(s2.eval(u, id));
                 }
                 return Ok(*v.get(v.len() - 1, &s2.arena.ast));
@@ -406,6 +1584,23 @@ impl Interpreter {
     pub fn print(&self, ast: &AST) {
         ast::print(ast, &self.arena);
     }
+
+    pub fn format(&self, ast: &AST) -> String {
+        ast::format(ast, &self.arena)
+    }
+
+    pub fn console(&self) -> Console {
+        self.arena.console
+    }
+
+    pub fn set_console(&mut self, rows: usize, cols: usize) {
+        self.arena.console.rows = rows;
+        self.arena.console.cols = cols;
+    }
+
+    pub fn set_precision(&mut self, precision: usize) {
+        self.arena.console.precision = precision;
+    }
 }
 
 #[repr(u8)]
@@ -414,6 +1609,26 @@ enum Natives {
     Parse,
     Eval,
     Debug,
+    Serialize,
+    Deserialize,
+    Enlist,
+    ToJson,
+    FromJson,
+    Hopen,
+    Hclose,
+    System,
+    Dir,
+    Exists,
+    Hdel,
+    Rename,
+    Hcount,
+    Mtime,
+    Getenv,
+    Setenv,
+    Splay,
+    Get,
+    Save,
+    Load,
 }
 
 pub fn new() -> Interpreter {
@@ -422,9 +1637,64 @@ pub fn new() -> Interpreter {
     arena.add_native("parse".to_string(), Natives::Parse as u8);
     arena.add_native("eval".to_string(), Natives::Eval as u8);
     arena.add_native("debug".to_string(), Natives::Debug as u8);
+    arena.add_native("serialize".to_string(), Natives::Serialize as u8);
+    arena.add_native("deserialize".to_string(), Natives::Deserialize as u8);
+    arena.add_native("enlist".to_string(), Natives::Enlist as u8);
+    arena.add_native("tojson".to_string(), Natives::ToJson as u8);
+    arena.add_native("fromjson".to_string(), Natives::FromJson as u8);
+    arena.add_native("hopen".to_string(), Natives::Hopen as u8);
+    arena.add_native("hclose".to_string(), Natives::Hclose as u8);
+    arena.add_native("system".to_string(), Natives::System as u8);
+    arena.add_native("dir".to_string(), Natives::Dir as u8);
+    arena.add_native("exists".to_string(), Natives::Exists as u8);
+    arena.add_native("hdel".to_string(), Natives::Hdel as u8);
+    arena.add_native("rename".to_string(), Natives::Rename as u8);
+    arena.add_native("hcount".to_string(), Natives::Hcount as u8);
+    arena.add_native("mtime".to_string(), Natives::Mtime as u8);
+    arena.add_native("getenv".to_string(), Natives::Getenv as u8);
+    arena.add_native("setenv".to_string(), Natives::Setenv as u8);
+    arena.add_native("splay".to_string(), Natives::Splay as u8);
+    arena.add_native("get".to_string(), Natives::Get as u8);
+    arena.add_native("save".to_string(), Natives::Save as u8);
+    arena.add_native("load".to_string(), Natives::Load as u8);
     Interpreter {
         parser: parser::new(),
         arena: arena,
         env: Environment::new_root(),
+        frames: Vec::new(),
+        callee: None,
+        debugger: Debugger::new(),
+        profiler: Profiler::new(),
+        tracer: Tracer::new(),
+        handles: Handles::new(),
+        mapped: 0,
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process;
+
+    // Globals saved by one interpreter load into another, locals of earlier
+    // calls are not globals.
+    #[test]
+    fn workspace() {
+        let path = env::temp_dir().join(format!("k-ws-{}", process::id()));
+        let p = path.to_str().unwrap();
+        let mut a = new();
+        a.eval_str("n:42").unwrap();
+        a.eval_str("f:{[x]x+n}").unwrap();
+        a.eval_str("g:{[x]y:x;y}").unwrap();
+        assert_eq!(a.eval_str("g 1").unwrap(), Value::Int(1));
+        a.eval_str("s:\"text\"").unwrap();
+        let names = a.save(p).unwrap();
+        assert_eq!(names, vec!["f", "g", "n", "s"]);
+        let mut b = new();
+        assert_eq!(b.load(p).unwrap(), names);
+        assert_eq!(b.eval_str("f 1").unwrap(), Value::Int(43));
+        assert_eq!(b.eval_str("s").unwrap(), Value::String("text".to_string()));
+        assert_eq!(b.eval_str("y").unwrap_err().to_string(), "'undefined");
+        fs::remove_file(&path).unwrap();
     }
-}
\ No newline at end of file
+}
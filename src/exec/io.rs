@@ -0,0 +1,374 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::mem;
+use std::process::{Command, Stdio};
+use std::time::UNIX_EPOCH;
+use std::f64::NAN;
+use exec::error::Error as ExecError;
+use parse::ast::NULL;
+use parse::temporal::{self, Temporal, EPOCH, NS_DAY};
+
+// Left or right operand of an ioverb, a standard handle (0 stdin, 1 stdout,
+// 2 stderr) or a file path.
+#[derive(Debug, Clone)]
+pub enum Target {
+    Fd(i64),
+    Path(String),
+}
+
+fn lines<R: BufRead>(r: R) -> Result<Vec<String>, ExecError> {
+    let mut v: Vec<String> = Vec::new();
+    for l in r.lines() {
+        let l = try!(l.map_err(|_| ExecError::Io));
+        v.push(l.trim_end_matches('\r').to_string());
+    }
+    Ok(v)
+}
+
+fn write<W: Write>(w: &mut W, v: &[String]) -> Result<(), ExecError> {
+    for l in v {
+        try!(writeln!(w, "{}", l).map_err(|_| ExecError::Io));
+    }
+    w.flush().map_err(|_| ExecError::Io)
+}
+
+pub fn read_text(t: &Target) -> Result<Vec<String>, ExecError> {
+    match *t {
+        Target::Fd(0) => {
+            let stdin = io::stdin();
+            let r = lines(stdin.lock());
+            r
+        }
+        Target::Fd(_) => Err(ExecError::Io),
+        Target::Path(ref p) => {
+            let f = try!(File::open(p).map_err(|_| ExecError::Io));
+            lines(BufReader::new(f))
+        }
+    }
+}
+
+pub fn write_text(t: &Target, v: &[String]) -> Result<(), ExecError> {
+    match *t {
+        Target::Fd(1) => write(&mut io::stdout(), v),
+        Target::Fd(2) => write(&mut io::stderr(), v),
+        Target::Fd(_) => Err(ExecError::Io),
+        Target::Path(ref p) => {
+            let mut f = try!(File::create(p).map_err(|_| ExecError::Io));
+            write(&mut f, v)
+        }
+    }
+}
+
+pub fn read_bytes(t: &Target) -> Result<Vec<u8>, ExecError> {
+    let mut v: Vec<u8> = Vec::new();
+    match *t {
+        Target::Fd(0) => {
+            try!(io::stdin().read_to_end(&mut v).map_err(|_| ExecError::Io));
+        }
+        Target::Fd(_) => return Err(ExecError::Io),
+        Target::Path(ref p) => {
+            let mut f = try!(File::open(p).map_err(|_| ExecError::Io));
+            try!(f.read_to_end(&mut v).map_err(|_| ExecError::Io));
+        }
+    }
+    Ok(v)
+}
+
+pub fn write_bytes(t: &Target, v: &[u8]) -> Result<(), ExecError> {
+    let r = match *t {
+        Target::Fd(1) => io::stdout().write_all(v).and_then(|_| io::stdout().flush()),
+        Target::Fd(2) => io::stderr().write_all(v),
+        Target::Fd(_) => return Err(ExecError::Io),
+        Target::Path(ref p) => File::create(p).and_then(|mut f| f.write_all(v)),
+    };
+    r.map_err(|_| ExecError::Io)
+}
+
+// Sorted names of the entries of directory p.
+pub fn dir(p: &str) -> Result<Vec<String>, ExecError> {
+    let mut r: Vec<String> = Vec::new();
+    for e in try!(fs::read_dir(p).map_err(|_| ExecError::Io)) {
+        let e = try!(e.map_err(|_| ExecError::Io));
+        r.push(e.file_name().to_string_lossy().into_owned());
+    }
+    r.sort();
+    Ok(r)
+}
+
+pub fn exists(p: &str) -> bool {
+    fs::metadata(p).is_ok()
+}
+
+// Deletes a file or an empty directory.
+pub fn delete(p: &str) -> Result<(), ExecError> {
+    let m = try!(fs::symlink_metadata(p).map_err(|_| ExecError::Io));
+    let r = if m.is_dir() { fs::remove_dir(p) } else { fs::remove_file(p) };
+    r.map_err(|_| ExecError::Io)
+}
+
+pub fn rename(from: &str, to: &str) -> Result<(), ExecError> {
+    fs::rename(from, to).map_err(|_| ExecError::Io)
+}
+
+pub fn size(p: &str) -> Result<i64, ExecError> {
+    fs::metadata(p).map(|m| m.len() as i64).map_err(|_| ExecError::Io)
+}
+
+// Modification time as a timestamp, nanoseconds since 2000.01.01.
+pub fn mtime(p: &str) -> Result<i64, ExecError> {
+    let t = try!(fs::metadata(p).and_then(|m| m.modified()).map_err(|_| ExecError::Io));
+    let ns = match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64 * temporal::NS_SECOND + d.subsec_nanos() as i64,
+        Err(e) => {
+            let d = e.duration();
+            -(d.as_secs() as i64 * temporal::NS_SECOND + d.subsec_nanos() as i64)
+        }
+    };
+    Ok(ns - EPOCH * NS_DAY)
+}
+
+// Runs cmd with sh and returns the lines it printed, stderr is passed through.
+// A failing command is Exit with its status, -1 if killed by a signal.
+pub fn system(cmd: &str) -> Result<Vec<String>, ExecError> {
+    let out = try!(Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|_| ExecError::Io));
+    if !out.status.success() {
+        return Err(ExecError::Exit(out.status.code().unwrap_or(-1)));
+    }
+    lines(&out.stdout[..])
+}
+
+// Field of a fixed width binary record, all little endian. Skip is a space in
+// the type string and ignores as many bytes as its width.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Skip(usize),
+    Bool,
+    Byte,
+    Short,
+    Int,
+    Long,
+    Real,
+    Float,
+}
+
+impl Field {
+    pub fn width(&self) -> usize {
+        match *self {
+            Field::Skip(w) => w,
+            Field::Bool | Field::Byte => 1,
+            Field::Short => 2,
+            Field::Int | Field::Real => 4,
+            Field::Long | Field::Float => 8,
+        }
+    }
+}
+
+// Decoded record field or delimited text column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    Bool(Vec<bool>),
+    Byte(Vec<u8>),
+    Int(Vec<i64>),
+    Float(Vec<f64>),
+    Symbol(Vec<String>),
+    Chars(Vec<String>),
+    Temporal(Temporal, Vec<i64>),
+}
+
+// Fields of a record spec such as ("ijf";4 8 8), widths must agree with types.
+pub fn fields(types: &str, widths: &[i64]) -> Result<Vec<Field>, ExecError> {
+    if types.len() != widths.len() {
+        return Err(ExecError::Length);
+    }
+    let mut r: Vec<Field> = Vec::new();
+    for (c, &w) in types.chars().zip(widths) {
+        if w <= 0 {
+            return Err(ExecError::Length);
+        }
+        let f = match c {
+            ' ' => Field::Skip(w as usize),
+            'b' => Field::Bool,
+            'x' => Field::Byte,
+            'h' => Field::Short,
+            'i' => Field::Int,
+            'j' => Field::Long,
+            'e' => Field::Real,
+            'f' => Field::Float,
+            _ => return Err(ExecError::Type),
+        };
+        if f.width() != w as usize {
+            return Err(ExecError::Length);
+        }
+        r.push(f);
+    }
+    Ok(r)
+}
+
+fn le(b: &[u8]) -> u64 {
+    b.iter().rev().fold(0u64, |a, &x| (a << 8) | x as u64)
+}
+
+// Splits data into records and returns one column per field.
+pub fn records(data: &[u8], fields: &[Field]) -> Result<Vec<Column>, ExecError> {
+    let size = fields.iter().fold(0, |a, f| a + f.width());
+    if size == 0 || data.len() % size != 0 {
+        return Err(ExecError::Length);
+    }
+    let n = data.len() / size;
+    let mut r: Vec<Column> = Vec::new();
+    let mut offset = 0;
+    for f in fields {
+        let w = f.width();
+        let cells = (0..n).map(|i| &data[i * size + offset..i * size + offset + w]);
+        match *f {
+            Field::Skip(_) => (),
+            Field::Bool => r.push(Column::Bool(cells.map(|b| b[0] != 0).collect())),
+            Field::Byte => r.push(Column::Byte(cells.map(|b| b[0]).collect())),
+            Field::Short => {
+                r.push(Column::Int(cells.map(|b| le(b) as u16 as i16 as i64).collect()))
+            }
+            Field::Int => r.push(Column::Int(cells.map(|b| le(b) as u32 as i32 as i64).collect())),
+            Field::Long => r.push(Column::Int(cells.map(|b| le(b) as i64).collect())),
+            Field::Real => {
+                r.push(Column::Float(cells.map(|b| f32::from_bits(le(b) as u32) as f64).collect()))
+            }
+            Field::Float => r.push(Column::Float(cells.map(|b| f64::from_bits(le(b))).collect())),
+        }
+        offset += w;
+    }
+    Ok(r)
+}
+
+// Splits delimited text into rows of fields. A field may be quoted with ",
+// then a doubled quote is a literal one and delimiters and newlines belong to
+// the field. Blank lines are skipped.
+pub fn split(text: &str, delim: char) -> Vec<Vec<String>> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c != '"' {
+                field.push(c);
+            } else if chars.peek() == Some(&'"') {
+                field.push('"');
+                chars.next();
+            } else {
+                quoted = false;
+            }
+        } else if c == '"' {
+            quoted = true;
+        } else if c == delim {
+            row.push(mem::replace(&mut field, String::new()));
+        } else if c == '\n' {
+            row.push(mem::replace(&mut field, String::new()));
+            if row.len() > 1 || !row[0].is_empty() {
+                rows.push(mem::replace(&mut row, Vec::new()));
+            } else {
+                row.clear();
+            }
+        } else if c != '\r' {
+            field.push(c);
+        }
+    }
+    if !row.is_empty() || !field.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+fn cells<'a>(rows: &'a [Vec<String>], i: usize) -> Vec<&'a str> {
+    rows.iter().map(|r| r.get(i).map(|s| &s[..]).unwrap_or("")).collect()
+}
+
+fn temporals(kind: Temporal, cells: &[&str]) -> Column {
+    let v = cells.iter()
+        .map(|s| match temporal::parse(s.trim()) {
+            Some((k, x)) if k == kind => x,
+            _ => NULL,
+        })
+        .collect();
+    Column::Temporal(kind, v)
+}
+
+// Boolean cell, empty is false as booleans have no null.
+fn boolean(s: &str) -> Result<bool, ExecError> {
+    match s.trim() {
+        "1" | "true" => Ok(true),
+        "" | "0" | "false" => Ok(false),
+        _ => Err(ExecError::Type),
+    }
+}
+
+// Byte cell in hex, empty is 0x00 as bytes have no null.
+fn byte(s: &str) -> Result<u8, ExecError> {
+    match s.trim() {
+        "" => Ok(0),
+        x => u8::from_str_radix(x, 16).map_err(|_| ExecError::Type),
+    }
+}
+
+// Column of type t, empty or unparsable cells are null. Booleans and bytes
+// have no null: an empty cell is 0b or 0x00 and any other unparsable cell is
+// a type error rather than a value that could be mistaken for data.
+fn column(t: char, cells: &[&str]) -> Result<Column, ExecError> {
+    let c = match t {
+        'B' => Column::Bool(try!(cells.iter().map(|s| boolean(s)).collect())),
+        'X' => Column::Byte(try!(cells.iter().map(|s| byte(s)).collect())),
+        'H' | 'I' | 'J' => {
+            Column::Int(cells.iter().map(|s| s.trim().parse::<i64>().unwrap_or(NULL)).collect())
+        }
+        'E' | 'F' => {
+            Column::Float(cells.iter().map(|s| s.trim().parse::<f64>().unwrap_or(NAN)).collect())
+        }
+        'S' => Column::Symbol(cells.iter().map(|s| s.to_string()).collect()),
+        '*' => Column::Chars(cells.iter().map(|s| s.to_string()).collect()),
+        'D' => temporals(Temporal::Date, cells),
+        'U' => temporals(Temporal::Minute, cells),
+        'V' => temporals(Temporal::Second, cells),
+        'T' => temporals(Temporal::Time, cells),
+        'P' => temporals(Temporal::Timestamp, cells),
+        'N' => temporals(Temporal::Timespan, cells),
+        _ => return Err(ExecError::Type),
+    };
+    Ok(c)
+}
+
+// Loads delimited text with one type per column, a space skips the column.
+// With a header the first row names the columns, the names of the columns
+// kept are returned along with them.
+pub fn delimited(text: &str,
+                 types: &str,
+                 delim: char,
+                 header: bool)
+                 -> Result<(Vec<String>, Vec<Column>), ExecError> {
+    let rows = split(text, delim);
+    let (head, body) = match rows.split_first() {
+        Some((h, b)) if header => (&h[..], b),
+        _ => (&[][..], &rows[..]),
+    };
+    if header && head.len() != types.chars().count() {
+        return Err(ExecError::Length);
+    }
+    let mut names: Vec<String> = Vec::new();
+    let mut cols: Vec<Column> = Vec::new();
+    for (i, t) in types.chars().enumerate() {
+        if t == ' ' {
+            continue;
+        }
+        cols.push(try!(column(t, &cells(body, i))));
+        if header {
+            names.push(head[i].trim().to_string());
+        }
+    }
+    Ok((names, cols))
+}
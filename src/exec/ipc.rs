@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use exec::error::Error as ExecError;
+
+// Message framing between processes: an 8 byte header followed by a value in
+// the binary form of exec::serial. The header is a 1 for little endian, the
+// kind, a 1 when a response carries an error, a zero and the total length
+// of the message as u32. An error response holds the error name as utf-8.
+// A connection starts with a login of user:password as utf-8 answered by an
+// empty response or an error, 'access when refused.
+const HEADER: usize = 8;
+
+// Messages beyond this are refused. The body is read as it arrives, so a
+// header alone never allocates its length.
+const MAX_LENGTH: usize = 1 << 26;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Async = 0,
+    Sync = 1,
+    Response = 2,
+    Login = 3,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub kind: Kind,
+    pub error: bool,
+    pub payload: Vec<u8>,
+}
+
+pub fn send<W: Write>(w: &mut W,
+                      kind: Kind,
+                      error: bool,
+                      payload: &[u8])
+                      -> Result<(), ExecError> {
+    let n = HEADER + payload.len();
+    if n > MAX_LENGTH {
+        return Err(ExecError::Length);
+    }
+    let mut b: Vec<u8> = vec![1, kind as u8, error as u8, 0];
+    for k in 0..4 {
+        b.push((n >> (8 * k)) as u8);
+    }
+    b.extend(payload);
+    w.write_all(&b).and_then(|_| w.flush()).map_err(|_| ExecError::Io)
+}
+
+pub fn recv<R: Read>(r: &mut R) -> Result<Message, ExecError> {
+    let mut h = [0u8; HEADER];
+    try!(r.read_exact(&mut h).map_err(|_| ExecError::Io));
+    let kind = match h[1] {
+        0 => Kind::Async,
+        1 => Kind::Sync,
+        2 => Kind::Response,
+        3 => Kind::Login,
+        _ => return Err(ExecError::Malformed),
+    };
+    let n = h[4..].iter().rev().fold(0usize, |a, &x| (a << 8) | x as usize);
+    if h[0] != 1 || n < HEADER || n > MAX_LENGTH {
+        return Err(ExecError::Malformed);
+    }
+    let mut payload: Vec<u8> = Vec::new();
+    try!(r.by_ref().take((n - HEADER) as u64).read_to_end(&mut payload).map_err(|_| ExecError::Io));
+    if payload.len() != n - HEADER {
+        return Err(ExecError::Io);
+    }
+    Ok(Message {
+        kind: kind,
+        error: h[2] != 0,
+        payload: payload,
+    })
+}
+
+// Address and credentials of host:port:user:password, a leading colon, the
+// host and the credentials are optional, an empty host or a bare port is
+// localhost.
+pub fn address(s: &str) -> (String, String) {
+    let s = if s.starts_with(':') { &s[1..] } else { s };
+    let v: Vec<&str> = s.splitn(3, ':').collect();
+    let (host, port, login) = match v.len() {
+        1 => ("localhost", v[0], ""),
+        2 => (v[0], v[1], ""),
+        _ => (v[0], v[1], v[2]),
+    };
+    let host = if host.is_empty() { "localhost" } else { host };
+    (format!("{}:{}", host, port), login.to_string())
+}
+
+// Connections opened with hopen, numbered from 3 so that handles never clash
+// with the standard ones of the ioverbs.
+pub struct Handles {
+    conns: HashMap<i64, TcpStream>,
+    next: i64,
+}
+
+impl Handles {
+    pub fn new() -> Handles {
+        Handles {
+            conns: HashMap::new(),
+            next: 3,
+        }
+    }
+
+    pub fn open(&mut self, address: &str, login: &str) -> Result<i64, ExecError> {
+        let mut s = try!(TcpStream::connect(address).map_err(|_| ExecError::Io));
+        let _ = s.set_nodelay(true);
+        try!(send(&mut s, Kind::Login, false, login.as_bytes()));
+        let m = try!(recv(&mut s));
+        if m.kind != Kind::Response {
+            return Err(ExecError::Access);
+        }
+        if m.error {
+            let e = String::from_utf8_lossy(&m.payload).into_owned();
+            return Err(ExecError::from_name(&e).unwrap_or(ExecError::Access));
+        }
+        let h = self.next;
+        self.next += 1;
+        self.conns.insert(h, s);
+        Ok(h)
+    }
+
+    pub fn close(&mut self, h: i64) -> bool {
+        self.conns.remove(&h).is_some()
+    }
+
+    pub fn contains(&self, h: i64) -> bool {
+        self.conns.contains_key(&h)
+    }
+
+    // Sends payload and for a sync message waits for the response. A dead
+    // connection is dropped.
+    pub fn request(&mut self, h: i64, kind: Kind, payload: &[u8]) -> Result<Message, ExecError> {
+        let r = match self.conns.get_mut(&h) {
+            Some(s) => {
+                send(s, kind, false, payload).and_then(|_| match kind {
+                    Kind::Sync => recv(s),
+                    _ => {
+                        Ok(Message {
+                            kind: Kind::Response,
+                            error: false,
+                            payload: Vec::new(),
+                        })
+                    }
+                })
+            }
+            None => return Err(ExecError::Undefined),
+        };
+        if r == Err(ExecError::Io) {
+            self.conns.remove(&h);
+        }
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn framing() {
+        let mut b: Vec<u8> = Vec::new();
+        send(&mut b, Kind::Sync, false, b"k\x01\x03").unwrap();
+        send(&mut b, Kind::Response, true, b"type").unwrap();
+        assert_eq!(&b[..8], &[1, 1, 0, 0, 11, 0, 0, 0]);
+        let mut r = Cursor::new(b);
+        assert_eq!(recv(&mut r).unwrap().payload, b"k\x01\x03".to_vec());
+        let m = recv(&mut r).unwrap();
+        assert_eq!((m.kind, m.error), (Kind::Response, true));
+        assert_eq!(recv(&mut r), Err(ExecError::Io));
+        let mut bad = Cursor::new(vec![1, 7, 0, 0, 8, 0, 0, 0]);
+        assert_eq!(recv(&mut bad), Err(ExecError::Malformed));
+        let mut short = Cursor::new(vec![1, 0, 0, 0, 4, 0, 0, 0]);
+        assert_eq!(recv(&mut short), Err(ExecError::Malformed));
+        let mut huge = Cursor::new(vec![1, 0, 0, 0, 0, 0, 0, 64]);
+        assert_eq!(recv(&mut huge), Err(ExecError::Malformed));
+        let mut cut = Cursor::new(vec![1, 0, 0, 0, 0, 0, 0, 1, 7]);
+        assert_eq!(recv(&mut cut), Err(ExecError::Io));
+    }
+
+    #[test]
+    fn loopback() {
+        let l = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = l.local_addr().unwrap().port();
+        let t = thread::spawn(move || {
+            let (mut s, _) = l.accept().unwrap();
+            let m = recv(&mut s).unwrap();
+            assert_eq!((m.kind, m.payload), (Kind::Login, b"u:p".to_vec()));
+            send(&mut s, Kind::Response, false, &[]).unwrap();
+            let m = recv(&mut s).unwrap();
+            send(&mut s, Kind::Response, false, &m.payload).unwrap();
+        });
+        let mut h = Handles::new();
+        let (a, login) = address(&format!(":localhost:{}:u:p", port));
+        let n = h.open(&a, &login).unwrap();
+        assert_eq!(n, 3);
+        assert_eq!(h.request(n, Kind::Sync, b"ping").unwrap().payload, b"ping".to_vec());
+        t.join().unwrap();
+        assert_eq!(h.request(n, Kind::Sync, b"ping"), Err(ExecError::Io));
+        assert!(!h.contains(n));
+    }
+
+    #[test]
+    fn addresses() {
+        let a = |s: &str| address(s);
+        assert_eq!(a("5001"), ("localhost:5001".to_string(), String::new()));
+        assert_eq!(a(":h:5001"), ("h:5001".to_string(), String::new()));
+        assert_eq!(a("::5001:u:p:q"), ("localhost:5001".to_string(), "u:p:q".to_string()));
+    }
+}
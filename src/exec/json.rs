@@ -0,0 +1,401 @@
+use std::char;
+use std::fmt::Write;
+use parse::ast::{self, AST, NULL};
+use parse::alloc::Arena;
+use parse::temporal;
+use exec::error::Error as ExecError;
+
+// Nesting beyond this is rejected instead of overflowing the stack.
+const MAX_DEPTH: usize = 256;
+
+// Dicts are objects, tables arrays of objects, symbols, strings and temporals
+// are strings and nulls are null. Functions have no JSON form.
+pub fn encode(ast: &AST, arena: &Arena) -> Result<String, ExecError> {
+    let mut w = String::new();
+    try!(value(&mut w, ast, arena));
+    Ok(w)
+}
+
+fn string(w: &mut String, s: &str) {
+    w.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => w.push_str("\\\""),
+            '\\' => w.push_str("\\\\"),
+            '\n' => w.push_str("\\n"),
+            '\r' => w.push_str("\\r"),
+            '\t' => w.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(w, "\\u{:04x}", c as u32);
+            }
+            c => w.push(c),
+        }
+    }
+    w.push('"');
+}
+
+fn key(ast: &AST, arena: &Arena) -> Result<String, ExecError> {
+    match *ast {
+        AST::Name { value: n } => Ok(arena.id_name(n)),
+        AST::Symbol { value: s } => Ok(arena.id_symbol(s)),
+        _ => arena.string_of(ast).ok_or(ExecError::Type),
+    }
+}
+
+fn value(w: &mut String, ast: &AST, arena: &Arena) -> Result<(), ExecError> {
+    match *ast {
+        AST::Nil => w.push_str("null"),
+        AST::Bool { value: b } => w.push_str(if b { "true" } else { "false" }),
+        AST::Byte { value: b } => {
+            let _ = write!(w, "{}", b);
+        }
+        AST::Int { value: NULL } => w.push_str("null"),
+        AST::Int { value: v } => {
+            let _ = write!(w, "{}", v);
+        }
+        AST::Float { value: v } if !v.is_finite() => w.push_str("null"),
+        AST::Float { value: v } => {
+            let _ = write!(w, "{}", v);
+        }
+        AST::Temporal { value: NULL, .. } => w.push_str("null"),
+        AST::Temporal { kind: k, value: v } => string(w, &temporal::format(k, v)),
+        AST::Symbol { value: s } => string(w, &arena.id_symbol(s)),
+        AST::String { .. } | AST::Text { .. } => {
+            string(w, &arena.string_of(ast).unwrap_or(String::new()))
+        }
+        AST::List { .. } |
+        AST::Mapped { .. } => {
+            w.push('[');
+            for (i, x) in ast::items(ast, arena).unwrap_or(Vec::new()).iter().enumerate() {
+                if i > 0 {
+                    w.push(',');
+                }
+                try!(value(w, x, arena));
+            }
+            w.push(']');
+        }
+        AST::Dict { keys: ref k, values: ref v } => {
+            w.push('{');
+            for (i, (x, y)) in k.iter(&arena.ast).zip(v.iter(&arena.ast)).enumerate() {
+                if i > 0 {
+                    w.push(',');
+                }
+                string(w, &try!(key(x, arena)));
+                w.push(':');
+                try!(value(w, y, arena));
+            }
+            w.push('}');
+        }
+        AST::Table { columns: ref c, values: ref v } => {
+            let mut names: Vec<String> = Vec::new();
+            let mut cols: Vec<Vec<AST>> = Vec::new();
+            for (x, y) in c.iter(&arena.ast).zip(v.iter(&arena.ast)) {
+                names.push(try!(key(x, arena)));
+                cols.push(try!(ast::items(y, arena).ok_or(ExecError::Type)));
+            }
+            let rows = cols.iter().map(|x| x.len()).min().unwrap_or(0);
+            w.push('[');
+            for i in 0..rows {
+                if i > 0 {
+                    w.push(',');
+                }
+                w.push('{');
+                for (j, (n, x)) in names.iter().zip(&cols).enumerate() {
+                    if j > 0 {
+                        w.push(',');
+                    }
+                    string(w, n);
+                    w.push(':');
+                    try!(value(w, &x[i], arena));
+                }
+                w.push('}');
+            }
+            w.push(']');
+        }
+        _ => return Err(ExecError::Type),
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+struct Reader<'a> {
+    s: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn space(&mut self) {
+        while self.pos < self.s.len() &&
+              (self.s[self.pos] == b' ' || self.s[self.pos] == b'\t' ||
+               self.s[self.pos] == b'\n' || self.s[self.pos] == b'\r') {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.space();
+        self.s.get(self.pos).map(|x| *x)
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), ExecError> {
+        match self.peek() {
+            Some(x) if x == c => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(ExecError::Malformed),
+        }
+    }
+
+    fn word(&mut self, w: &str, v: Json) -> Result<Json, ExecError> {
+        if self.s[self.pos..].starts_with(w.as_bytes()) {
+            self.pos += w.len();
+            return Ok(v);
+        }
+        Err(ExecError::Malformed)
+    }
+
+    fn value(&mut self) -> Result<Json, ExecError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ExecError::Malformed);
+        }
+        match self.peek() {
+            Some(b'n') => self.word("null", Json::Null),
+            Some(b't') => self.word("true", Json::Bool(true)),
+            Some(b'f') => self.word("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.pos += 1;
+                self.depth += 1;
+                let mut v: Vec<Json> = Vec::new();
+                if self.peek() != Some(b']') {
+                    loop {
+                        v.push(try!(self.value()));
+                        if self.peek() != Some(b',') {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                self.depth -= 1;
+                try!(self.expect(b']'));
+                Ok(Json::Array(v))
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                self.depth += 1;
+                let mut v: Vec<(String, Json)> = Vec::new();
+                if self.peek() != Some(b'}') {
+                    loop {
+                        if self.peek() != Some(b'"') {
+                            return Err(ExecError::Malformed);
+                        }
+                        let k = try!(self.string());
+                        try!(self.expect(b':'));
+                        v.push((k, try!(self.value())));
+                        if self.peek() != Some(b',') {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                self.depth -= 1;
+                try!(self.expect(b'}'));
+                Ok(Json::Object(v))
+            }
+            Some(c) if c == b'-' || (c as char).is_digit(10) => self.number(),
+            _ => Err(ExecError::Malformed),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, ExecError> {
+        let start = self.pos;
+        while self.pos < self.s.len() {
+            match self.s[self.pos] as char {
+                c if c.is_digit(10) => self.pos += 1,
+                '-' | '+' | '.' | 'e' | 'E' => self.pos += 1,
+                _ => break,
+            }
+        }
+        let t = String::from_utf8_lossy(&self.s[start..self.pos]).into_owned();
+        if let Ok(i) = t.parse::<i64>() {
+            return Ok(Json::Int(i));
+        }
+        t.parse::<f64>().map(Json::Float).map_err(|_| ExecError::Malformed)
+    }
+
+    fn hex(&mut self) -> Result<u32, ExecError> {
+        if self.s.len() - self.pos < 4 {
+            return Err(ExecError::Malformed);
+        }
+        let t = String::from_utf8_lossy(&self.s[self.pos..self.pos + 4]).into_owned();
+        self.pos += 4;
+        u32::from_str_radix(&t, 16).map_err(|_| ExecError::Malformed)
+    }
+
+    fn string(&mut self) -> Result<String, ExecError> {
+        try!(self.expect(b'"'));
+        let mut b: Vec<u8> = Vec::new();
+        loop {
+            let c = match self.s.get(self.pos) {
+                Some(&c) => c,
+                None => return Err(ExecError::Malformed),
+            };
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let e = match self.s.get(self.pos) {
+                        Some(&e) => e,
+                        None => return Err(ExecError::Malformed),
+                    };
+                    self.pos += 1;
+                    let u = match e {
+                        b'"' | b'\\' | b'/' => e as u32,
+                        b'b' => 8,
+                        b'f' => 12,
+                        b'n' => 10,
+                        b'r' => 13,
+                        b't' => 9,
+                        b'u' => {
+                            let h = try!(self.hex());
+                            if h >= 0xd800 && h < 0xdc00 {
+                                // surrogate pair
+                                if !self.s[self.pos..].starts_with(b"\\u") {
+                                    return Err(ExecError::Malformed);
+                                }
+                                self.pos += 2;
+                                let l = try!(self.hex());
+                                if l < 0xdc00 || l >= 0xe000 {
+                                    return Err(ExecError::Malformed);
+                                }
+                                0x10000 + ((h - 0xd800) << 10) + (l - 0xdc00)
+                            } else {
+                                h
+                            }
+                        }
+                        _ => return Err(ExecError::Malformed),
+                    };
+                    let c = try!(char::from_u32(u).ok_or(ExecError::Malformed));
+                    let mut t = String::new();
+                    t.push(c);
+                    b.extend(t.as_bytes());
+                }
+                c => b.push(c),
+            }
+        }
+        String::from_utf8(b).map_err(|_| ExecError::Malformed)
+    }
+}
+
+fn parse(s: &str) -> Result<Json, ExecError> {
+    let mut r = Reader {
+        s: s.as_bytes(),
+        pos: 0,
+        depth: 0,
+    };
+    let v = try!(r.value());
+    if r.peek().is_some() {
+        return Err(ExecError::Malformed);
+    }
+    Ok(v)
+}
+
+// Objects are dicts, arrays of objects with the same keys are tables, arrays
+// of numbers, booleans or strings are typed lists. Integral numbers are ints,
+// others floats and null is the float null.
+pub fn decode(s: &str, arena: &mut Arena) -> Result<AST, ExecError> {
+    let j = try!(parse(s));
+    to_ast(j, arena)
+}
+
+fn to_ast(j: Json, arena: &mut Arena) -> Result<AST, ExecError> {
+    match j {
+        Json::Null => Ok(AST::Float { value: ::std::f64::NAN }),
+        Json::Bool(b) => Ok(AST::Bool { value: b }),
+        Json::Int(i) => Ok(AST::Int { value: i }),
+        Json::Float(f) => Ok(AST::Float { value: f }),
+        Json::String(s) => Ok(arena.string(s)),
+        Json::Array(v) => {
+            if let Some(keys) = columns(&v) {
+                let mut cols: Vec<Vec<Json>> = keys.iter().map(|_| Vec::new()).collect();
+                for x in v {
+                    if let Json::Object(o) = x {
+                        for (i, (_, y)) in o.into_iter().enumerate() {
+                            cols[i].push(y);
+                        }
+                    }
+                }
+                let mut values: Vec<AST> = Vec::new();
+                for c in cols {
+                    values.push(try!(to_ast(Json::Array(c), arena)));
+                }
+                let names: Vec<AST> = keys.into_iter().map(|k| arena.intern_symbol(k)).collect();
+                return Ok(ast::table(&mut arena.ast, names, values));
+            }
+            let float = floats(&v);
+            let mut r: Vec<AST> = Vec::new();
+            for x in v {
+                r.push(match x {
+                    Json::Int(i) if float => AST::Float { value: i as f64 },
+                    x => try!(to_ast(x, arena)),
+                });
+            }
+            let curry = !r.is_empty() &&
+                        r.iter().all(|x| x.is_atom() && x.type_id() == r[0].type_id());
+            Ok(ast::list(curry, &mut arena.ast, r))
+        }
+        Json::Object(o) => {
+            let mut keys: Vec<AST> = Vec::new();
+            let mut values: Vec<AST> = Vec::new();
+            for (k, v) in o {
+                keys.push(arena.intern_name(k));
+                values.push(try!(to_ast(v, arena)));
+            }
+            Ok(ast::dict(&mut arena.ast, keys, values))
+        }
+    }
+}
+
+// Numbers mixing ints with floats or nulls are all floats.
+fn floats(v: &[Json]) -> bool {
+    let number = |x: &Json| match *x {
+        Json::Int(_) | Json::Float(_) | Json::Null => true,
+        _ => false,
+    };
+    let float = |x: &Json| match *x {
+        Json::Float(_) | Json::Null => true,
+        _ => false,
+    };
+    v.iter().all(number) && v.iter().any(float)
+}
+
+// Keys shared in the same order by every object of a non empty array.
+fn columns(v: &[Json]) -> Option<Vec<String>> {
+    let keys = |x: &Json| match *x {
+        Json::Object(ref o) if !o.is_empty() => {
+            Some(o.iter().map(|&(ref k, _)| k.clone()).collect::<Vec<String>>())
+        }
+        _ => None,
+    };
+    let first: Vec<String> = match v.first().and_then(|x| keys(x)) {
+        Some(k) => k,
+        None => return None,
+    };
+    if v.iter().all(|x| keys(x).as_ref() == Some(&first)) {
+        Some(first)
+    } else {
+        None
+    }
+}
@@ -1,4 +1,13 @@
 pub mod error;
 pub mod otree;
 pub mod env;
-pub mod i10;
\ No newline at end of file
+pub mod i10;
+pub mod debug;
+pub mod profile;
+pub mod trace;
+pub mod io;
+pub mod serial;
+pub mod json;
+pub mod ipc;
+pub mod splay;
+pub mod value;
\ No newline at end of file
@@ -1,17 +1,19 @@
 use std::fmt;
 use std::fmt::Debug;
 
+// A node holds its own items, so that a scope can still define names after
+// scopes below it were added.
 #[derive(PartialEq, Clone, Debug)]
-struct Node {
-    bounds: (usize, usize),
+struct Node<T> {
+    items: Vec<T>,
     parent: Option<usize>,
 }
 
-impl fmt::Display for Node {
+impl<T> fmt::Display for Node<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.parent {
-            Some(parent) => write!(f, "[{:?}—{:?}]", self.bounds, parent),
-            _ => write!(f, "[{:?}—(root)]", self.bounds),
+            Some(parent) => write!(f, "[{:?}—{:?}]", self.items.len(), parent),
+            _ => write!(f, "[{:?}—(root)]", self.items.len()),
         }
     }
 }
@@ -22,8 +24,7 @@ pub struct Id(pub usize);
 
 #[derive(Debug)]
 pub struct Tree<T> {
-    nodes: Vec<Node>,
-    items: Vec<T>,
+    nodes: Vec<Node<T>>,
 }
 
 // TODO: Remove T: Debug
@@ -31,53 +32,54 @@ impl<T: Debug> Tree<T> {
     pub fn with_capacity(cap: usize) -> Self {
         let mut n = Vec::with_capacity(cap);
         n.push(Node {
-            bounds: (0, 0),
+            items: Vec::with_capacity(cap),
             parent: None,
         });
-        Tree {
-            nodes: n,
-            items: Vec::with_capacity(cap),
-        }
+        Tree { nodes: n }
     }
 
     pub fn len(&self) -> (usize, usize) {
-        (self.nodes.len(), self.items.len())
+        (self.nodes.len(), self.nodes.iter().map(|n| n.items.len()).sum())
     }
 
     pub fn dump(&self) {
-        for i in self.items[0..self.items.len()].iter() {
-            println!("item {:?}", i);
+        for n in self.nodes.iter() {
+            for i in n.items.iter() {
+                println!("item {:?}", i);
+            }
         }
-        for i in self.nodes[0..self.nodes.len()].iter() {
-            println!("node {}", i);
+        for n in self.nodes.iter() {
+            println!("node {}", n);
         }
     }
 
+    #[inline]
+    pub fn root(&self) -> Id {
+        Id(0)
+    }
+
     #[inline]
     pub fn last(&self) -> Id {
         Id(self.nodes.len() - 1)
     }
 
     pub fn append_node(&mut self, n: Id) -> Id {
-        let bound = self.nodes.last().expect("There is no root in otree.").bounds.1;
         self.nodes.push(Node {
-            bounds: (bound, bound),
+            items: Vec::new(),
             parent: Some(n.0),
         });
         Id(self.nodes.len() - 1)
     }
 
-    pub fn insert(&mut self, item: T) {
-        self.items.push(item);
-        let mut n = self.nodes.last_mut().unwrap();
-        n.bounds.1 += 1;
+    pub fn insert(&mut self, n: Id, item: T) {
+        self.nodes.get_mut(n.0).expect("Error getting node.").items.push(item);
     }
 
     pub fn get<'a, F>(&'a self, n: Id, mut f: F) -> Option<(&'a T, Id)>
         where for<'r> F: FnMut(&'r &T) -> bool
     {
         let nd = self.nodes.get(n.0).expect("Error getting node.");
-        for i in self.items[nd.bounds.0..nd.bounds.1].iter().rev() {
+        for i in nd.items.iter().rev() {
             if f(&i) {
                 return Some((i, n));
             }
@@ -88,16 +90,75 @@ impl<T: Debug> Tree<T> {
         }
     }
 
-    pub fn clean(&mut self) -> usize {
-        let l = self.items.len();
-        unsafe {
-            self.items.set_len(0);
-            self.nodes.set_len(0);
+    // Items of node n and its ancestors, most recent first.
+    pub fn scope(&self, n: Id) -> Vec<&T> {
+        let mut r: Vec<&T> = Vec::new();
+        let mut n = Some(n.0);
+        while let Some(i) = n {
+            let nd = self.nodes.get(i).expect("Error getting node.");
+            r.extend(nd.items.iter().rev());
+            n = nd.parent;
         }
-        self.nodes.push(Node {
-            bounds: (0, 0),
-            parent: None,
-        });
+        r
+    }
+
+    pub fn clean(&mut self) -> usize {
+        let l = self.len().1;
+        self.nodes.truncate(1);
+        self.nodes[0].items.clear();
         l
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(t: &Tree<(char, i32)>, n: Id, k: char) -> Option<(i32, Id)> {
+        t.get(n, |x| x.0 == k).map(|(x, i)| (x.1, i))
+    }
+
+    // A child sees the items of its ancestors unless it has its own.
+    #[test]
+    fn shadowing() {
+        let mut t: Tree<(char, i32)> = Tree::with_capacity(4);
+        let root = t.root();
+        t.insert(root, ('a', 1));
+        let f = t.append_node(root);
+        assert_eq!(find(&t, f, 'a'), Some((1, root)));
+        t.insert(f, ('a', 2));
+        assert_eq!(find(&t, f, 'a'), Some((2, f)));
+        assert_eq!(find(&t, root, 'a'), Some((1, root)));
+        assert_eq!(t.scope(f), vec![&('a', 2), &('a', 1)]);
+    }
+
+    // Items added to a node after a child was appended are still its own.
+    #[test]
+    fn insert_below() {
+        let mut t: Tree<(char, i32)> = Tree::with_capacity(4);
+        let root = t.root();
+        let f = t.append_node(root);
+        let g = t.append_node(f);
+        t.insert(root, ('b', 1));
+        t.insert(f, ('c', 2));
+        assert_eq!(find(&t, g, 'b'), Some((1, root)));
+        assert_eq!(find(&t, g, 'c'), Some((2, f)));
+        assert_eq!(find(&t, root, 'c'), None);
+        assert_eq!(t.last(), g);
+    }
+
+    #[test]
+    fn clean() {
+        let mut t: Tree<(char, i32)> = Tree::with_capacity(4);
+        let root = t.root();
+        t.insert(root, ('a', 1));
+        let f = t.append_node(root);
+        t.insert(f, ('b', 2));
+        assert_eq!(t.clean(), 2);
+        assert_eq!(t.len(), (1, 0));
+        assert_eq!(t.last(), root);
+        t.insert(root, ('a', 3));
+        let f = t.append_node(root);
+        assert_eq!(find(&t, f, 'a'), Some((3, root)));
+    }
+}
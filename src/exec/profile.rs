@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+// What a profile entry is charged to: a lambda by the global it was called
+// through (None when anonymous) or a primitive verb.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Lambda(Option<u16>),
+    Verb(u8),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub calls: u64,
+    pub inclusive: u64, // nanoseconds
+    pub exclusive: u64,
+    pub alloc: usize, // arena cells allocated, exclusive
+}
+
+struct Open {
+    key: Key,
+    start: Instant,
+    arena: usize,
+    child_time: u64,
+    child_alloc: usize,
+}
+
+pub struct Profiler {
+    pub enabled: bool,
+    entries: HashMap<Key, Entry>,
+    stack: Vec<Open>,
+}
+
+fn nanos(t: &Instant) -> u64 {
+    let d = t.elapsed();
+    d.as_secs() * 1000000000 + d.subsec_nanos() as u64
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            enabled: false,
+            entries: HashMap::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.entries.clear();
+        self.stack.clear();
+    }
+
+    pub fn enter(&mut self, key: Key, arena: usize) {
+        self.stack.push(Open {
+            key: key,
+            start: Instant::now(),
+            arena: arena,
+            child_time: 0,
+            child_alloc: 0,
+        });
+    }
+
+    pub fn exit(&mut self, arena: usize) {
+        let o = match self.stack.pop() {
+            Some(o) => o,
+            None => return,
+        };
+        let time = nanos(&o.start);
+        // the arena is reset by gc, do not count that as negative allocation
+        let alloc = if arena > o.arena { arena - o.arena } else { 0 };
+        if let Some(p) = self.stack.last_mut() {
+            p.child_time += time;
+            p.child_alloc += alloc;
+        }
+        let e = self.entries.entry(o.key).or_insert(Entry {
+            calls: 0,
+            inclusive: 0,
+            exclusive: 0,
+            alloc: 0,
+        });
+        e.calls += 1;
+        // recursive calls are already included in the outer activation
+        if !self.stack.iter().any(|x| x.key == o.key) {
+            e.inclusive += time;
+        }
+        e.exclusive += time - o.child_time.min(time);
+        e.alloc += alloc - o.child_alloc.min(alloc);
+    }
+
+    // Entries sorted by exclusive time, most expensive first.
+    pub fn report(&self) -> Vec<(Key, Entry)> {
+        let mut r: Vec<(Key, Entry)> = self.entries.iter().map(|(k, e)| (*k, *e)).collect();
+        r.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive));
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exec::i10;
+
+    #[test]
+    fn nesting() {
+        let mut p = Profiler::new();
+        let f = Key::Lambda(Some(0));
+        p.enter(f, 0);
+        p.enter(Key::Verb(b'+'), 10);
+        p.exit(15);
+        p.enter(f, 15);
+        p.exit(18);
+        p.exit(20);
+        let r: HashMap<Key, Entry> = p.report().into_iter().collect();
+        let (e, v) = (r[&f], r[&Key::Verb(b'+')]);
+        assert_eq!((e.calls, v.calls), (2, 1));
+        assert_eq!((e.alloc, v.alloc), (15, 5));
+        assert!(e.inclusive >= e.exclusive);
+        p.reset();
+        assert!(p.report().is_empty());
+    }
+
+    #[test]
+    fn interpreter() {
+        let mut i = i10::new();
+        i.eval_str("fac:{$[x=1;1;x*fac[x-1]]}").unwrap();
+        i.set_profile(true);
+        i.eval_str("fac 5").unwrap();
+        i.eval_str("{[x]x+1}[1]").unwrap();
+        let r: HashMap<String, Entry> = i.profile().into_iter().collect();
+        assert_eq!(r["fac"].calls, 5);
+        assert_eq!(r["*"].calls, 4);
+        assert_eq!(r["lambda"].calls, 1);
+        i.set_profile(false);
+        i.eval_str("fac 5").unwrap();
+        assert_eq!(i.profile().into_iter().find(|x| x.0 == "fac").unwrap().1.calls, 5);
+        i.set_profile(true);
+        assert!(i.profile().is_empty());
+    }
+}
@@ -0,0 +1,543 @@
+use parse::ast::{self, AST, Adverb, Args, Chars};
+use parse::alloc::Arena;
+use parse::temporal::Temporal;
+use exec::error::Error as ExecError;
+
+// Binary form of a value: a magic byte, the format version and the value as a
+// tag byte followed by its payload. Integers are little endian, counts are
+// u32 and names, symbols and strings are a count followed by utf-8. Lambdas
+// and the expressions in their bodies are written as trees with every name
+// spelled out, so they load into any interpreter.
+pub const VERSION: u8 = 1;
+const MAGIC: u8 = b'k';
+
+// Nesting beyond this is rejected instead of overflowing the stack.
+const MAX_DEPTH: usize = 256;
+
+const NIL: u8 = 0;
+const BOOL: u8 = 1;
+const BYTE: u8 = 2;
+const INT: u8 = 3;
+const FLOAT: u8 = 4;
+const SYMBOL: u8 = 5;
+const STRING: u8 = 6;
+const TEMPORAL: u8 = 7;
+const NAME: u8 = 8;
+const LIST: u8 = 9;
+const VECTOR: u8 = 10; // list of atoms of one type: element tag, count, payloads
+const DICT: u8 = 11;
+const LAMBDA: u8 = 12;
+const VERB: u8 = 13;
+const IOVERB: u8 = 14;
+const NATIVE: u8 = 15;
+const SEQUENCE: u8 = 16;
+const NAMEREF: u8 = 17;
+const ADVERB: u8 = 18;
+const CONDITION: u8 = 19;
+const QUIT: u8 = 20;
+const TABLE: u8 = 21;
+
+// Partitioned tables are views of local files and have no binary form.
+pub fn encode(ast: &AST, arena: &Arena) -> Result<Vec<u8>, ExecError> {
+    let mut w: Vec<u8> = vec![MAGIC, VERSION];
+    try!(value(&mut w, ast, arena));
+    Ok(w)
+}
+
+fn int(w: &mut Vec<u8>, v: i64) {
+    for k in 0..8 {
+        w.push((v >> (8 * k)) as u8);
+    }
+}
+
+fn count(w: &mut Vec<u8>, n: usize) {
+    for k in 0..4 {
+        w.push((n >> (8 * k)) as u8);
+    }
+}
+
+fn text(w: &mut Vec<u8>, s: &str) {
+    count(w, s.len());
+    w.extend(s.as_bytes());
+}
+
+fn tag(ast: &AST) -> u8 {
+    match *ast {
+        AST::Bool { .. } => BOOL,
+        AST::Byte { .. } => BYTE,
+        AST::Int { .. } => INT,
+        AST::Float { .. } => FLOAT,
+        AST::Symbol { .. } => SYMBOL,
+        _ => NIL,
+    }
+}
+
+// Element tag when a list can be written as a typed vector.
+fn vector_tag(v: &[AST]) -> Option<u8> {
+    match v.first().map(tag) {
+        Some(NIL) | None => None,
+        Some(t) if v.iter().all(|x| tag(x) == t) => Some(t),
+        _ => None,
+    }
+}
+
+fn values(w: &mut Vec<u8>, v: &[AST], arena: &Arena) -> Result<(), ExecError> {
+    count(w, v.len());
+    for x in v {
+        try!(value(w, x, arena));
+    }
+    Ok(())
+}
+
+// Payload of an atom that may be an element of a typed vector.
+fn scalar(w: &mut Vec<u8>, ast: &AST, arena: &Arena) {
+    match *ast {
+        AST::Bool { value: v } => w.push(v as u8),
+        AST::Byte { value: v } => w.push(v),
+        AST::Int { value: v } => int(w, v),
+        AST::Float { value: v } => int(w, v.to_bits() as i64),
+        AST::Symbol { value: v } => text(w, &arena.id_symbol(v)),
+        _ => (),
+    }
+}
+
+fn items(w: &mut Vec<u8>, curry: bool, s: &[AST], arena: &Arena) -> Result<(), ExecError> {
+    match vector_tag(s) {
+        Some(t) if curry => {
+            w.push(VECTOR);
+            w.push(t);
+            count(w, s.len());
+            for x in s {
+                scalar(w, x, arena);
+            }
+        }
+        _ => {
+            w.push(LIST);
+            w.push(curry as u8);
+            try!(values(w, s, arena));
+        }
+    }
+    Ok(())
+}
+
+fn value(w: &mut Vec<u8>, ast: &AST, arena: &Arena) -> Result<(), ExecError> {
+    match *ast {
+        AST::Bool { .. } |
+        AST::Byte { .. } |
+        AST::Int { .. } |
+        AST::Float { .. } |
+        AST::Symbol { .. } => {
+            w.push(tag(ast));
+            scalar(w, ast, arena);
+        }
+        AST::String { .. } | AST::Text { .. } => {
+            w.push(STRING);
+            text(w, &arena.string_of(ast).unwrap_or(String::new()));
+        }
+        AST::Temporal { kind: k, value: v } => {
+            w.push(TEMPORAL);
+            w.push(k.type_id() as u8);
+            int(w, v);
+        }
+        AST::Name { value: v } => {
+            w.push(NAME);
+            text(w, &arena.id_name(v));
+        }
+        AST::List { curry: c, values: ref v } => try!(items(w, c, v.as_slice(&arena.ast), arena)),
+        AST::Mapped { id: n } => try!(items(w, true, &arena.column(n).items(), arena)),
+        AST::Dict { keys: ref k, values: ref v } => {
+            w.push(DICT);
+            try!(values(w, k.as_slice(&arena.ast), arena));
+            try!(values(w, v.as_slice(&arena.ast), arena));
+        }
+        AST::Table { columns: ref c, values: ref v } => {
+            w.push(TABLE);
+            try!(values(w, c.as_slice(&arena.ast), arena));
+            try!(values(w, v.as_slice(&arena.ast), arena));
+        }
+        AST::Lambda { args: ref a, body: b } => {
+            w.push(LAMBDA);
+            w.push(a.len() as u8);
+            for n in a.iter().take(a.len()) {
+                text(w, &arena.id_name(*n));
+            }
+            try!(value(w, arena.ast.deref(b), arena));
+        }
+        AST::Verb { kind: k, args: ref a } => {
+            w.push(VERB);
+            w.push(k);
+            try!(values(w, a.as_slice(&arena.ast), arena));
+        }
+        AST::Ioverb { fd: f } => {
+            w.push(IOVERB);
+            w.push(f);
+        }
+        AST::Native { name: n } => {
+            w.push(NATIVE);
+            text(w, &arena.id_name(n));
+        }
+        AST::Sequence { values: ref v } => {
+            w.push(SEQUENCE);
+            try!(values(w, v.as_slice(&arena.ast), arena));
+        }
+        AST::Nameref { name: n, value: v } => {
+            w.push(NAMEREF);
+            text(w, &arena.id_name(n));
+            try!(value(w, arena.ast.deref(v), arena));
+        }
+        AST::Adverb { kind: ref k, left: l, verb: v, right: r } => {
+            w.push(ADVERB);
+            text(w, &k.to_string());
+            try!(value(w, arena.ast.deref(l), arena));
+            try!(value(w, arena.ast.deref(v), arena));
+            try!(value(w, arena.ast.deref(r), arena));
+        }
+        AST::Condition { list: ref l } => {
+            w.push(CONDITION);
+            try!(values(w, l.as_slice(&arena.ast), arena));
+        }
+        AST::Debug { value: v } => try!(value(w, arena.ast.deref(v), arena)),
+        AST::Partitioned { .. } => return Err(ExecError::Type),
+        AST::Quit => w.push(QUIT),
+        AST::Nil => w.push(NIL),
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    b: &'a [u8],
+    pos: usize,
+    depth: usize,
+    // expressions are accepted, otherwise only inside lambda bodies
+    code: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, ExecError> {
+        match self.b.get(self.pos) {
+            Some(&x) => {
+                self.pos += 1;
+                Ok(x)
+            }
+            None => Err(ExecError::Malformed),
+        }
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], ExecError> {
+        if self.b.len() - self.pos < n {
+            return Err(ExecError::Malformed);
+        }
+        let r = &self.b[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(r)
+    }
+
+    fn int(&mut self) -> Result<i64, ExecError> {
+        let b = try!(self.bytes(8));
+        Ok(b.iter().rev().fold(0u64, |a, &x| (a << 8) | x as u64) as i64)
+    }
+
+    // Every element takes at least a byte, a larger count is malformed and
+    // must not be allocated for.
+    fn count(&mut self) -> Result<usize, ExecError> {
+        let b = try!(self.bytes(4));
+        let n = b.iter().rev().fold(0usize, |a, &x| (a << 8) | x as usize);
+        if n > self.b.len() - self.pos {
+            return Err(ExecError::Malformed);
+        }
+        Ok(n)
+    }
+
+    fn text(&mut self) -> Result<String, ExecError> {
+        let n = try!(self.count());
+        let b = try!(self.bytes(n));
+        String::from_utf8(b.to_vec()).map_err(|_| ExecError::Malformed)
+    }
+}
+
+pub fn decode(b: &[u8], arena: &mut Arena) -> Result<AST, ExecError> {
+    decode_with(b, arena, true)
+}
+
+// Like decode but refuses expressions outside lambda bodies, 'type, so that
+// a value from another process evaluates to itself wherever it is passed.
+pub fn decode_value(b: &[u8], arena: &mut Arena) -> Result<AST, ExecError> {
+    decode_with(b, arena, false)
+}
+
+fn decode_with(b: &[u8], arena: &mut Arena, code: bool) -> Result<AST, ExecError> {
+    if b.len() < 2 || b[0] != MAGIC {
+        return Err(ExecError::Malformed);
+    }
+    if b[1] != VERSION {
+        return Err(ExecError::Version);
+    }
+    let mut r = Reader {
+        b: b,
+        pos: 2,
+        depth: 0,
+        code: code,
+    };
+    let v = try!(read(&mut r, arena));
+    if r.pos != b.len() {
+        return Err(ExecError::Malformed);
+    }
+    Ok(v)
+}
+
+fn read(r: &mut Reader, arena: &mut Arena) -> Result<AST, ExecError> {
+    if r.depth >= MAX_DEPTH {
+        return Err(ExecError::Malformed);
+    }
+    r.depth += 1;
+    let t = try!(r.byte());
+    let v = payload(r, t, arena);
+    r.depth -= 1;
+    v
+}
+
+fn list(r: &mut Reader, arena: &mut Arena) -> Result<Vec<AST>, ExecError> {
+    let n = try!(r.count());
+    let mut v: Vec<AST> = Vec::with_capacity(n);
+    for _ in 0..n {
+        v.push(try!(read(r, arena)));
+    }
+    Ok(v)
+}
+
+fn payload(r: &mut Reader, t: u8, arena: &mut Arena) -> Result<AST, ExecError> {
+    match t {
+        NAME | SEQUENCE | NAMEREF | ADVERB | CONDITION | QUIT if !r.code => Err(ExecError::Type),
+        NIL => Ok(AST::Nil),
+        BOOL => {
+            match try!(r.byte()) {
+                0 => Ok(AST::Bool { value: false }),
+                1 => Ok(AST::Bool { value: true }),
+                _ => Err(ExecError::Malformed),
+            }
+        }
+        BYTE => Ok(AST::Byte { value: try!(r.byte()) }),
+        INT => Ok(AST::Int { value: try!(r.int()) }),
+        FLOAT => Ok(AST::Float { value: f64::from_bits(try!(r.int()) as u64) }),
+        SYMBOL => Ok(arena.intern_symbol(try!(r.text()))),
+        STRING => {
+            let s = try!(r.text());
+            Ok(arena.string(s))
+        }
+        TEMPORAL => {
+            let k = try!(Temporal::from_type_id(try!(r.byte()) as i8).ok_or(ExecError::Malformed));
+            Ok(AST::Temporal {
+                kind: k,
+                value: try!(r.int()),
+            })
+        }
+        NAME => Ok(arena.intern_name(try!(r.text()))),
+        LIST => {
+            let c = match try!(r.byte()) {
+                0 => false,
+                1 => true,
+                _ => return Err(ExecError::Malformed),
+            };
+            let v = try!(list(r, arena));
+            Ok(ast::list(c, &mut arena.ast, v))
+        }
+        VECTOR => {
+            let e = try!(r.byte());
+            match e {
+                BOOL | BYTE | INT | FLOAT | SYMBOL => (),
+                _ => return Err(ExecError::Malformed),
+            }
+            let n = try!(r.count());
+            let mut v: Vec<AST> = Vec::with_capacity(n);
+            for _ in 0..n {
+                v.push(try!(payload(r, e, arena)));
+            }
+            Ok(ast::list(true, &mut arena.ast, v))
+        }
+        DICT => {
+            // keys are names, never evaluated
+            let code = r.code;
+            r.code = true;
+            let k = list(r, arena);
+            r.code = code;
+            let k = try!(k);
+            if k.iter().any(|x| match *x {
+                AST::Name { .. } | AST::Symbol { .. } => false,
+                _ => !code,
+            }) {
+                return Err(ExecError::Type);
+            }
+            let v = try!(list(r, arena));
+            if k.len() != v.len() {
+                return Err(ExecError::Malformed);
+            }
+            Ok(ast::dict(&mut arena.ast, k, v))
+        }
+        TABLE => {
+            let c = try!(list(r, arena));
+            let v = try!(list(r, arena));
+            if c.len() != v.len() {
+                return Err(ExecError::Malformed);
+            }
+            Ok(ast::table(&mut arena.ast, c, v))
+        }
+        LAMBDA => {
+            let n = try!(r.byte());
+            if n > 8 {
+                return Err(ExecError::Malformed);
+            }
+            let mut a = Args::new();
+            for _ in 0..n {
+                a.push(arena.intern_name_id(try!(r.text())));
+            }
+            let code = r.code;
+            r.code = true;
+            let b = read(r, arena);
+            r.code = code;
+            let b = try!(b);
+            Ok(AST::Lambda {
+                args: a,
+                body: ast::atom(&mut arena.ast, b),
+            })
+        }
+        VERB => {
+            let k = try!(r.byte());
+            let v = try!(list(r, arena));
+            if !v.is_empty() && !r.code {
+                return Err(ExecError::Type);
+            }
+            Ok(AST::Verb {
+                kind: k,
+                args: ast::vector(&mut arena.ast, v),
+            })
+        }
+        IOVERB => Ok(AST::Ioverb { fd: try!(r.byte()) }),
+        NATIVE => {
+            let s = try!(r.text());
+            arena.native_id(&s).map(|n| AST::Native { name: n }).ok_or(ExecError::Malformed)
+        }
+        SEQUENCE => {
+            let v = try!(list(r, arena));
+            if v.is_empty() {
+                return Err(ExecError::Malformed);
+            }
+            Ok(ast::sequence(&mut arena.ast, v))
+        }
+        NAMEREF => {
+            let n = arena.intern_name_id(try!(r.text()));
+            let v = try!(read(r, arena));
+            Ok(AST::Nameref {
+                name: n,
+                value: ast::atom(&mut arena.ast, v),
+            })
+        }
+        ADVERB => {
+            let k = try!(try!(r.text()).parse::<Adverb>().map_err(|_| ExecError::Malformed));
+            let l = try!(read(r, arena));
+            let v = try!(read(r, arena));
+            let x = try!(read(r, arena));
+            Ok(AST::Adverb {
+                kind: k,
+                left: ast::atom(&mut arena.ast, l),
+                verb: ast::atom(&mut arena.ast, v),
+                right: ast::atom(&mut arena.ast, x),
+            })
+        }
+        CONDITION => {
+            let v = try!(list(r, arena));
+            Ok(AST::Condition { list: ast::vector(&mut arena.ast, v) })
+        }
+        QUIT => Ok(AST::Quit),
+        _ => Err(ExecError::Malformed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse::parser;
+
+    fn samples(arena: &mut Arena) -> Vec<AST> {
+        let mut p = parser::new();
+        ["1 2 3",
+         "(1;`a;\"abc\";2.5)",
+         "[a:1;b:`c`d]",
+         "2024.01.31D09:30:00.000000000",
+         "0x0102ff",
+         "{[x;y]z:x+y;$[z>1;z;x]}"]
+            .iter()
+            .map(|s| p.parse_str(s, arena).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut arena = Arena::new();
+        for x in samples(&mut arena) {
+            let b = encode(&x, &arena).unwrap();
+            let y = decode(&b, &mut arena).unwrap();
+            assert_eq!(encode(&y, &arena).unwrap(), b);
+        }
+    }
+
+    #[test]
+    fn version() {
+        let mut arena = Arena::new();
+        let mut b = encode(&AST::Int { value: 1 }, &arena).unwrap();
+        b[1] = VERSION + 1;
+        assert_eq!(decode(&b, &mut arena), Err(ExecError::Version));
+    }
+
+    // Truncated, extended and randomly corrupted input must be rejected or
+    // decoded, never panic or allocate without bound.
+    #[test]
+    fn malformed() {
+        let mut arena = Arena::new();
+        let mut seed: u64 = 0x2545f4914f6cdd1d;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        for x in samples(&mut arena) {
+            let b = encode(&x, &arena).unwrap();
+            for n in 0..b.len() {
+                assert!(decode(&b[..n], &mut arena).is_err());
+            }
+            let mut e = b.clone();
+            e.push(0);
+            assert!(decode(&e, &mut arena).is_err());
+            for _ in 0..2000 {
+                let mut m = b.clone();
+                for _ in 0..1 + next() % 4 {
+                    let i = 2 + (next() as usize) % (m.len() - 2);
+                    m[i] = next() as u8;
+                }
+                let _ = decode(&m, &mut arena);
+            }
+        }
+        let mut deep = vec![MAGIC, VERSION];
+        for _ in 0..100000 {
+            deep.extend(&[LIST, 0, 1, 0, 0, 0]);
+        }
+        assert_eq!(decode(&deep, &mut arena), Err(ExecError::Malformed));
+    }
+
+    #[test]
+    fn values_only() {
+        let mut arena = Arena::new();
+        let mut p = parser::new();
+        for s in &["1 2 3", "[a:1;b:`c]", "{x+y}", "(1;\"a\";{z:x;z})"] {
+            let x = p.parse_str(s, &mut arena).unwrap();
+            let b = encode(&x, &arena).unwrap();
+            assert!(decode_value(&b, &mut arena).is_ok(), "{}", s);
+        }
+        for s in &["1+2", "a", "a:1", "(1;a)", "[a:1+2]", "(1;2+3)"] {
+            let x = p.parse_str(s, &mut arena).unwrap();
+            let b = encode(&x, &arena).unwrap();
+            assert!(decode(&b, &mut arena).is_ok(), "{}", s);
+            assert_eq!(decode_value(&b, &mut arena), Err(ExecError::Type), "{}", s);
+        }
+    }
+}
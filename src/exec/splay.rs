@@ -0,0 +1,265 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use parse::ast::{self, AST};
+use parse::alloc::Arena;
+use parse::mapped::{Elem, Mapped, Partitioned};
+use parse::temporal::{self, Temporal};
+use exec::error::Error as ExecError;
+use exec::serial;
+
+// A splayed table is a directory with .d, the serialized column names, .sym,
+// the serialized symbols that symbol columns index, and one file per column.
+// A column of one atom type is a 16 byte header and its raw little endian
+// elements, mapped when loaded: "kc", the element tag of exec::serial, the
+// temporal type or 0, a u32 and the count as u64. Symbols are stored as u32
+// indices into .sym and the u32 of their header is the number of symbols
+// they index, a column is refused when .sym is shorter; it is 0 for other
+// types. Other columns are serialized and read whole.
+const MAGIC: &'static [u8] = b"kc";
+const HEADER: usize = 16;
+
+const BOOL: u8 = 1;
+const BYTE: u8 = 2;
+const INT: u8 = 3;
+const FLOAT: u8 = 4;
+const SYMBOL: u8 = 5;
+const TEMPORAL: u8 = 7;
+
+fn io<T>(r: ::std::io::Result<T>) -> Result<T, ExecError> {
+    r.map_err(|_| ExecError::Io)
+}
+
+fn elem(x: &AST) -> Option<Elem> {
+    match *x {
+        AST::Bool { .. } => Some(Elem::Bool),
+        AST::Byte { .. } => Some(Elem::Byte),
+        AST::Int { .. } => Some(Elem::Int),
+        AST::Float { .. } => Some(Elem::Float),
+        AST::Temporal { kind: k, .. } => Some(Elem::Temporal(k)),
+        AST::Symbol { .. } => Some(Elem::Symbol),
+        _ => None,
+    }
+}
+
+// Element type shared by every item of a non empty column.
+fn column_elem(v: &[AST]) -> Option<Elem> {
+    match v.first().and_then(elem) {
+        Some(e) if v.iter().all(|x| elem(x) == Some(e)) => Some(e),
+        _ => None,
+    }
+}
+
+fn header(e: Elem, n: usize) -> Vec<u8> {
+    let (tag, kind) = match e {
+        Elem::Bool => (BOOL, 0),
+        Elem::Byte => (BYTE, 0),
+        Elem::Int => (INT, 0),
+        Elem::Float => (FLOAT, 0),
+        Elem::Temporal(k) => (TEMPORAL, k.type_id() as u8),
+        Elem::Symbol => (SYMBOL, 0),
+    };
+    let mut w: Vec<u8> = MAGIC.to_vec();
+    w.extend(&[tag, kind, 0, 0, 0, 0]);
+    for k in 0..8 {
+        w.push(((n as u64) >> (8 * k)) as u8);
+    }
+    w
+}
+
+// Element type, count and number of symbols indexed.
+fn parse_header(h: &[u8]) -> Option<(Elem, usize, usize)> {
+    if h.len() < HEADER || &h[..2] != MAGIC {
+        return None;
+    }
+    let e = match h[2] {
+        BOOL => Elem::Bool,
+        BYTE => Elem::Byte,
+        INT => Elem::Int,
+        FLOAT => Elem::Float,
+        SYMBOL => Elem::Symbol,
+        TEMPORAL => {
+            match Temporal::from_type_id(h[3] as i8) {
+                Some(k) => Elem::Temporal(k),
+                None => return None,
+            }
+        }
+        _ => return None,
+    };
+    let s = h[4..8].iter().rev().fold(0u64, |a, &x| (a << 8) | x as u64);
+    let n = h[8..16].iter().rev().fold(0u64, |a, &x| (a << 8) | x as u64);
+    Some((e, n as usize, s as usize))
+}
+
+fn mapped_column(v: &[AST], e: Elem, domain: &mut Vec<u16>) -> Vec<u8> {
+    let mut w = header(e, v.len());
+    for x in v {
+        let (b, width): (u64, usize) = match *x {
+            AST::Bool { value: b } => (b as u64, 1),
+            AST::Byte { value: b } => (b as u64, 1),
+            AST::Int { value: i } => (i as u64, 8),
+            AST::Float { value: f } => (f.to_bits(), 8),
+            AST::Temporal { value: t, .. } => (t as u64, 8),
+            AST::Symbol { value: s } => {
+                let i = match domain.iter().position(|x| *x == s) {
+                    Some(i) => i,
+                    None => {
+                        domain.push(s);
+                        domain.len() - 1
+                    }
+                };
+                (i as u64, 4)
+            }
+            _ => (0, 0),
+        };
+        for k in 0..width {
+            w.push((b >> (8 * k)) as u8);
+        }
+    }
+    if e == Elem::Symbol {
+        for k in 0..4 {
+            w[4 + k] = ((domain.len() as u32) >> (8 * k)) as u8;
+        }
+    }
+    w
+}
+
+// Writes a new file and renames it over path, a column of the table being
+// replaced may still be mapped and must not be truncated under it. Column
+// names do not start with a dot, so neither does a temporary name clash.
+fn write_file(path: &Path, b: &[u8]) -> Result<(), ExecError> {
+    let name = try!(path.file_name().ok_or(ExecError::Io)).to_string_lossy().into_owned();
+    let tmp = path.with_file_name(format!(".{}.tmp", name));
+    try!(io(File::create(&tmp).and_then(|mut f| f.write_all(b))));
+    io(fs::rename(&tmp, path))
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, ExecError> {
+    let mut b: Vec<u8> = Vec::new();
+    try!(io(File::open(path).and_then(|mut f| f.read_to_end(&mut b))));
+    Ok(b)
+}
+
+// Writes table t to directory dir, .d last so that a partly written table
+// does not load.
+pub fn write(dir: &str, t: &AST, arena: &mut Arena) -> Result<(), ExecError> {
+    let (columns, values) = match *t {
+        AST::Table { columns: ref c, values: ref v } => {
+            (c.as_slice(&arena.ast).to_vec(), v.as_slice(&arena.ast).to_vec())
+        }
+        _ => return Err(ExecError::Type),
+    };
+    let root = Path::new(dir);
+    try!(io(fs::create_dir_all(root)));
+    let mut domain: Vec<u16> = Vec::new();
+    for (c, v) in columns.iter().zip(&values) {
+        let name = match *c {
+            AST::Symbol { value: s } => arena.id_symbol(s),
+            _ => return Err(ExecError::Type),
+        };
+        if name.is_empty() || name.starts_with('.') || name.contains('/') {
+            return Err(ExecError::Type);
+        }
+        let items = try!(ast::items(v, arena).ok_or(ExecError::Type));
+        let b = match column_elem(&items) {
+            Some(e) => mapped_column(&items, e, &mut domain),
+            None => try!(serial::encode(v, arena)),
+        };
+        try!(write_file(&root.join(&name), &b));
+    }
+    let syms: Vec<AST> = domain.into_iter().map(|s| AST::Symbol { value: s }).collect();
+    let syms = ast::list(true, &mut arena.ast, syms);
+    try!(write_file(&root.join(".sym"), &try!(serial::encode(&syms, arena))));
+    let names = ast::list(true, &mut arena.ast, columns);
+    write_file(&root.join(".d"), &try!(serial::encode(&names, arena)))
+}
+
+fn symbols(path: &Path, arena: &mut Arena) -> Result<Vec<u16>, ExecError> {
+    let b = try!(read_file(path));
+    let l = try!(serial::decode(&b, arena));
+    let mut r: Vec<u16> = Vec::new();
+    for x in try!(ast::items(&l, arena).ok_or(ExecError::Malformed)) {
+        match x {
+            AST::Symbol { value: s } => r.push(s),
+            _ => return Err(ExecError::Malformed),
+        }
+    }
+    Ok(r)
+}
+
+// Loads the table in directory dir, typed columns are mapped.
+pub fn read(dir: &str, arena: &mut Arena) -> Result<AST, ExecError> {
+    let root = Path::new(dir);
+    let names = try!(symbols(&root.join(".d"), arena));
+    let domain = if root.join(".sym").exists() {
+        try!(symbols(&root.join(".sym"), arena))
+    } else {
+        Vec::new()
+    };
+    let mut columns: Vec<AST> = Vec::new();
+    let mut values: Vec<AST> = Vec::new();
+    for n in names {
+        let path = root.join(arena.id_symbol(n));
+        let mut h: Vec<u8> = Vec::new();
+        try!(io(File::open(&path).and_then(|f| f.take(HEADER as u64).read_to_end(&mut h))));
+        let v = match parse_header(&h) {
+            Some((e, len, syms)) => {
+                if syms > domain.len() {
+                    return Err(ExecError::Malformed);
+                }
+                let d = if e == Elem::Symbol { domain.clone() } else { Vec::new() };
+                let p = try!(path.to_str().ok_or(ExecError::Io));
+                let m = try!(Mapped::open(p, HEADER, e, len, d).map_err(|_| ExecError::Malformed));
+                arena.mapped.push(Some(m));
+                AST::Mapped { id: (arena.mapped.len() - 1) as u32 }
+            }
+            None => try!(serial::decode(&try!(read_file(&path)), arena)),
+        };
+        columns.push(AST::Symbol { value: n });
+        values.push(v);
+    }
+    Ok(ast::table(&mut arena.ast, columns, values))
+}
+
+// Finds the partitions root/yyyy.mm.dd/table of a date partitioned database.
+pub fn partitioned(root: &str, table: &str) -> Result<Partitioned, ExecError> {
+    let mut parts: Vec<(i64, String)> = Vec::new();
+    for e in try!(io(fs::read_dir(root))) {
+        let d = try!(io(e)).file_name().to_string_lossy().into_owned();
+        if let Some((Temporal::Date, v)) = temporal::parse(&d) {
+            if Path::new(root).join(&d).join(table).join(".d").exists() {
+                parts.push((v, d));
+            }
+        }
+    }
+    if parts.is_empty() {
+        return Err(ExecError::Io);
+    }
+    parts.sort();
+    Ok(Partitioned {
+        root: root.to_string(),
+        table: table.to_string(),
+        dates: parts.iter().map(|x| x.0).collect(),
+        dirs: parts.iter().map(|x| x.1.clone()).collect(),
+        loaded: parts.iter().map(|_| None).collect(),
+    })
+}
+
+// Splayed table of the partition in dir with the date as its first column.
+pub fn partition(dir: &str, date: i64, arena: &mut Arena) -> Result<AST, ExecError> {
+    let t = try!(read(dir, arena));
+    let (mut columns, mut values) = match t {
+        AST::Table { columns: c, values: v } => {
+            (c.as_slice(&arena.ast).to_vec(), v.as_slice(&arena.ast).to_vec())
+        }
+        _ => return Err(ExecError::Malformed),
+    };
+    let rows = values.first().and_then(|v| ast::count(v, arena)).unwrap_or(0);
+    let date = AST::Temporal {
+        kind: Temporal::Date,
+        value: date,
+    };
+    columns.insert(0, arena.intern_symbol("date".to_string()));
+    values.insert(0, ast::list(true, &mut arena.ast, vec![date; rows]));
+    Ok(ast::table(&mut arena.ast, columns, values))
+}
@@ -0,0 +1,53 @@
+// Evaluation trace, filter holds the names of the functions to trace in,
+// empty means everything.
+pub struct Tracer {
+    pub enabled: bool,
+    pub filter: Vec<u16>,
+    pub depth: usize,
+}
+
+impl Tracer {
+    pub fn new() -> Tracer {
+        Tracer {
+            enabled: false,
+            filter: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    pub fn line(&self, s: &str) {
+        println!("{}{}", " ".repeat(self.depth), s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exec::debug::{Step, Stop};
+    use exec::i10::{self, Interpreter};
+    use exec::value::Value;
+
+    fn off(i: &mut Interpreter, _: Stop) -> Step {
+        i.set_trace(false, &[]);
+        Step::Run
+    }
+
+    fn on(i: &mut Interpreter, _: Stop) -> Step {
+        i.set_trace(true, &[]);
+        Step::Run
+    }
+
+    // Turned on or off from a breakpoint, the call being traced returns.
+    #[test]
+    fn toggle_inside_call() {
+        let mut i = i10::new();
+        i.eval_str("f:{[x]x+1}").unwrap();
+        i.set_breakpoint("f", true);
+        i.set_hook(Some(off));
+        i.set_trace(true, &[]);
+        assert_eq!(i.eval_str("f 1").unwrap(), Value::Int(2));
+        i.set_hook(Some(on));
+        assert_eq!(i.eval_str("f 2").unwrap(), Value::Int(3));
+        i.set_hook(Some(off));
+        assert_eq!(i.eval_str("1+f 3").unwrap(), Value::Int(5));
+    }
+}
@@ -0,0 +1,301 @@
+use std::fmt;
+use parse::ast::{self, AST};
+use parse::alloc::Arena;
+use parse::error::Error as ParseError;
+use parse::temporal::Temporal;
+use exec::error::Error as ExecError;
+
+// Owned copy of a result for Rust hosts, nothing in it points into the arena
+// of the interpreter. Lists whose items are atoms of one type become vectors,
+// dicts and tables are keyed by name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Byte(u8),
+    Int(i64),
+    Float(f64),
+    Symbol(String),
+    String(String),
+    Temporal(Temporal, i64),
+    Bools(Vec<bool>),
+    Bytes(Vec<u8>),
+    Ints(Vec<i64>),
+    Floats(Vec<f64>),
+    Symbols(Vec<String>),
+    Temporals(Temporal, Vec<i64>),
+    List(Vec<Value>),
+    Dict(Vec<String>, Vec<Value>),
+    Table(Vec<String>, Vec<Value>),
+    Function(Function),
+}
+
+// Handle of a function, or of anything else that is not data such as a
+// partitioned table. Only valid for the interpreter that returned it, pass
+// it back to Interpreter::call_value. Handles are equal when they are the
+// same result of the same interpreter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    id: ast::Id,
+    owner: usize,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Parse(ParseError),
+    Exec(ExecError),
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Error {
+        Error::Parse(e)
+    }
+}
+
+impl From<ExecError> for Error {
+    fn from(e: ExecError) -> Error {
+        Error::Exec(e)
+    }
+}
+
+// As printed by the REPL, 'type.
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Parse(ref e) => write!(f, "'{}", format!("{:?}", e).to_lowercase()),
+            Error::Exec(ref e) => write!(f, "'{}", e.name()),
+        }
+    }
+}
+
+fn vector(v: &[Value]) -> Option<Value> {
+    macro_rules! all {
+        ($variant:ident, $to:ident) => {{
+            let mut r = Vec::new();
+            for x in v {
+                match *x {
+                    Value::$variant(ref a) => r.push(a.clone()),
+                    _ => return None,
+                }
+            }
+            Some(Value::$to(r))
+        }}
+    }
+    match v.first() {
+        Some(&Value::Bool(_)) => all!(Bool, Bools),
+        Some(&Value::Byte(_)) => all!(Byte, Bytes),
+        Some(&Value::Int(_)) => all!(Int, Ints),
+        Some(&Value::Float(_)) => all!(Float, Floats),
+        Some(&Value::Symbol(_)) => all!(Symbol, Symbols),
+        Some(&Value::Temporal(k, _)) => {
+            let mut r = Vec::new();
+            for x in v {
+                match *x {
+                    Value::Temporal(t, a) if t == k => r.push(a),
+                    _ => return None,
+                }
+            }
+            Some(Value::Temporals(k, r))
+        }
+        _ => None,
+    }
+}
+
+// Dict keys are names, table columns symbols.
+fn key(x: &AST, arena: &Arena) -> String {
+    match *x {
+        AST::Name { value: n } => arena.id_name(n),
+        AST::Symbol { value: s } => arena.id_symbol(s),
+        _ => String::new(),
+    }
+}
+
+pub fn from_ast(x: &AST, arena: &mut Arena) -> Value {
+    match *x {
+        AST::Bool { value: v } => Value::Bool(v),
+        AST::Byte { value: v } => Value::Byte(v),
+        AST::Int { value: v } => Value::Int(v),
+        AST::Float { value: v } => Value::Float(v),
+        AST::Symbol { value: v } => Value::Symbol(arena.id_symbol(v)),
+        AST::String { .. } | AST::Text { .. } => {
+            Value::String(arena.string_of(x).unwrap_or(String::new()))
+        }
+        AST::Temporal { kind: k, value: v } => Value::Temporal(k, v),
+        AST::List { .. } |
+        AST::Mapped { .. } => {
+            let items = ast::items(x, arena).unwrap_or(Vec::new());
+            let v: Vec<Value> = items.iter().map(|a| from_ast(a, arena)).collect();
+            vector(&v).unwrap_or(Value::List(v))
+        }
+        AST::Dict { keys: ref k, values: ref v } => {
+            let k = k.iter(&arena.ast).map(|a| key(a, arena)).collect();
+            let v = v.as_slice(&arena.ast).to_vec();
+            Value::Dict(k, v.iter().map(|a| from_ast(a, arena)).collect())
+        }
+        AST::Table { columns: ref c, values: ref v } => {
+            let c = c.iter(&arena.ast).map(|a| key(a, arena)).collect();
+            let v = v.as_slice(&arena.ast).to_vec();
+            Value::Table(c, v.iter().map(|a| from_ast(a, arena)).collect())
+        }
+        AST::Nil | AST::Quit => Value::Nil,
+        _ => {
+            Value::Function(Function {
+                id: arena.ast.push(*x),
+                owner: arena.owner,
+            })
+        }
+    }
+}
+
+pub fn to_ast(x: &Value, arena: &mut Arena) -> Result<AST, ExecError> {
+    let r = match *x {
+        Value::Nil => AST::Nil,
+        Value::Bool(v) => AST::Bool { value: v },
+        Value::Byte(v) => AST::Byte { value: v },
+        Value::Int(v) => AST::Int { value: v },
+        Value::Float(v) => AST::Float { value: v },
+        Value::Symbol(ref s) => arena.intern_symbol(s.clone()),
+        Value::String(ref s) => arena.string(s.clone()),
+        Value::Temporal(k, v) => AST::Temporal { kind: k, value: v },
+        Value::Bools(ref v) => {
+            let v = v.iter().map(|&a| AST::Bool { value: a }).collect();
+            ast::list(true, &mut arena.ast, v)
+        }
+        Value::Bytes(ref v) => {
+            let v = v.iter().map(|&a| AST::Byte { value: a }).collect();
+            ast::list(true, &mut arena.ast, v)
+        }
+        Value::Ints(ref v) => {
+            let v = v.iter().map(|&a| AST::Int { value: a }).collect();
+            ast::list(true, &mut arena.ast, v)
+        }
+        Value::Floats(ref v) => {
+            let v = v.iter().map(|&a| AST::Float { value: a }).collect();
+            ast::list(true, &mut arena.ast, v)
+        }
+        Value::Symbols(ref v) => {
+            let v = v.iter().map(|a| arena.intern_symbol(a.clone())).collect();
+            ast::list(true, &mut arena.ast, v)
+        }
+        Value::Temporals(k, ref v) => {
+            let v = v.iter()
+                .map(|&a| {
+                    AST::Temporal {
+                        kind: k,
+                        value: a,
+                    }
+                })
+                .collect();
+            ast::list(true, &mut arena.ast, v)
+        }
+        Value::List(ref v) => {
+            let v = try!(to_asts(v, arena));
+            ast::list(false, &mut arena.ast, v)
+        }
+        Value::Dict(ref k, ref v) => {
+            if k.len() != v.len() {
+                return Err(ExecError::Length);
+            }
+            let k = k.iter().map(|a| arena.intern_name(a.clone())).collect();
+            let v = try!(to_asts(v, arena));
+            ast::dict(&mut arena.ast, k, v)
+        }
+        Value::Table(ref c, ref v) => {
+            if c.len() != v.len() {
+                return Err(ExecError::Length);
+            }
+            let c = c.iter().map(|a| arena.intern_symbol(a.clone())).collect();
+            let v = try!(to_asts(v, arena));
+            ast::table(&mut arena.ast, c, v)
+        }
+        Value::Function(ref f) => {
+            if f.owner != arena.owner || f.id as usize >= arena.ast.len() {
+                return Err(ExecError::Undefined);
+            }
+            *arena.ast.deref(f.id)
+        }
+    };
+    Ok(r)
+}
+
+fn to_asts(v: &[Value], arena: &mut Arena) -> Result<Vec<AST>, ExecError> {
+    let mut r: Vec<AST> = Vec::new();
+    for x in v {
+        r.push(try!(to_ast(x, arena)));
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exec::i10;
+
+    #[test]
+    fn results() {
+        let mut i = i10::new();
+        assert_eq!(i.eval_str("1+2").unwrap(), Value::Int(3));
+        assert_eq!(i.eval_str("1 2 3+1").unwrap(), Value::Ints(vec![2, 3, 4]));
+        assert_eq!(i.eval_str("`a`b").unwrap(),
+                   Value::Symbols(vec!["a".to_string(), "b".to_string()]));
+        assert_eq!(i.eval_str("(1;`a;\"xy\")").unwrap(),
+                   Value::List(vec![Value::Int(1),
+                                    Value::Symbol("a".to_string()),
+                                    Value::String("xy".to_string())]));
+        assert_eq!(i.eval_str("[a:1;b:2.5]").unwrap(),
+                   Value::Dict(vec!["a".to_string(), "b".to_string()],
+                               vec![Value::Int(1), Value::Float(2.5)]));
+        assert_eq!(i.eval_str("2024.01.31").unwrap(),
+                   Value::Temporal(Temporal::Date, 8796));
+        match i.eval_str("1+`a") {
+            Err(e @ Error::Exec(ExecError::Type)) => assert_eq!(e.to_string(), "'type"),
+            r => panic!("{:?}", r),
+        }
+        assert!(i.eval_str("1+").is_err());
+        let long = "k".repeat(100);
+        assert_eq!(i.eval_str(&format!("\"{}\"", long)).unwrap(), Value::String(long));
+    }
+
+    #[test]
+    fn functions() {
+        let mut i = i10::new();
+        let f = match i.eval_str("{x+y}").unwrap() {
+            Value::Function(f) => f,
+            r => panic!("{:?}", r),
+        };
+        i.eval_str("a:0").unwrap();
+        assert_eq!(i.call_value(&f, &[Value::Int(1), Value::Ints(vec![2, 3])]).unwrap(),
+                   Value::Ints(vec![3, 4]));
+        assert_eq!(i.call_value(&f, &[Value::Int(1), Value::Symbol("a".to_string())])
+                       .unwrap_err()
+                       .to_string(),
+                   "'type");
+        let g = match i.eval_str("{x=y}").unwrap() {
+            Value::Function(g) => g,
+            r => panic!("{:?}", r),
+        };
+        let long = Value::String("k".repeat(100));
+        assert_eq!(i.call_value(&g, &[long.clone(), long]).unwrap(), Value::Bool(true));
+    }
+
+    // A handle of one interpreter is refused by another, whatever it holds.
+    #[test]
+    fn foreign_functions() {
+        let mut i = i10::new();
+        let mut j = i10::new();
+        let f = i.eval_str("{x+1}").unwrap();
+        let g = j.eval_str("{x+2}").unwrap();
+        assert!(f != g);
+        let f = match f {
+            Value::Function(f) => f,
+            r => panic!("{:?}", r),
+        };
+        assert_eq!(i.call_value(&f, &[Value::Int(1)]).unwrap(), Value::Int(2));
+        assert_eq!(j.call_value(&f, &[Value::Int(1)]).unwrap_err().to_string(),
+                   "'undefined");
+        assert_eq!(j.call_value(&f, &[Value::Function(f.clone())])
+                       .unwrap_err()
+                       .to_string(),
+                   "'undefined");
+    }
+}
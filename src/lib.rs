@@ -15,6 +15,7 @@ extern crate stacker;
 extern crate core;
 extern crate num;
 extern crate alloc;
+extern crate libc;
 
 pub mod handle;
 pub mod parse;
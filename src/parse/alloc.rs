@@ -1,21 +1,61 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 use parse::arena::ArenaMem;
-use parse::ast::{self, AST};
+use parse::ast::{self, AST, Chars, Console};
+use parse::mapped::{Mapped, Partitioned};
+
+static ARENAS: AtomicUsize = ATOMIC_USIZE_INIT;
 
 pub struct Arena {
+    // Tells arenas apart, handles given to Rust hosts carry it.
+    pub owner: usize,
     pub names: HashMap<String, u16>,
     pub symbols: HashMap<String, u16>,
     pub ast: ArenaMem<AST, ast::Id>,
     pub natives: HashMap<u16, u8>,
+    pub console: Console,
+    pub mapped: Vec<Option<Mapped>>,
+    pub partitioned: Vec<Partitioned>,
+    pub texts: Vec<String>,
 }
 
 impl Arena {
     pub fn new() -> Arena {
         Arena {
+            owner: ARENAS.fetch_add(1, Ordering::Relaxed),
             names: HashMap::new(),
             symbols: HashMap::new(),
             ast: ArenaMem::with_capacity(100),
             natives: HashMap::new(),
+            console: Console::new(),
+            mapped: Vec::new(),
+            partitioned: Vec::new(),
+            texts: Vec::new(),
+        }
+    }
+
+    // String atom of any length, those that do not fit Chars are kept in
+    // texts, such as long lines read from files.
+    pub fn string(&mut self, s: String) -> AST {
+        match Chars::new(&s) {
+            Some(c) => AST::String { value: c },
+            None => {
+                self.texts.push(s);
+                AST::Text { id: (self.texts.len() - 1) as u32 }
+            }
+        }
+    }
+
+    // Mapped column n, released ones are no longer referenced.
+    pub fn column(&self, n: u32) -> &Mapped {
+        self.mapped[n as usize].as_ref().expect("Released column.")
+    }
+
+    pub fn string_of(&self, ast: &AST) -> Option<String> {
+        match *ast {
+            AST::String { value: s } => Some(s.to_string()),
+            AST::Text { id: n } => self.texts.get(n as usize).cloned(),
+            _ => None,
         }
     }
 
@@ -6,6 +6,8 @@ use std::slice::Iter;
 use parse::alloc::Arena;
 use parse::arena::ArenaMem;
 use parse::vector::Vector;
+use parse::temporal::{self, Temporal};
+use parse::mapped::Elem;
 use handle;
 
 #[derive(Debug, Clone, Copy)]
@@ -131,7 +133,7 @@ impl Chars {
     }
 
     pub fn to_string(&self) -> String {
-        match str::from_utf8(&self.raw[..]) {
+        match str::from_utf8(&self.raw[..self.len as usize]) {
             Ok(s) => s.to_string(),
             Err(..) => "".to_string(),            
         }
@@ -155,13 +157,16 @@ impl fmt::Debug for Chars {
 #[derive(Debug, Clone, Copy)]
 pub enum AST {
     Bool { value: bool },
+    Byte { value: u8 },
     Name { value: u16 },
     Symbol { value: u16 },
     String { value: Chars },
+    Text { id: u32 },
     Verb { kind: u8, args: Vector<AST, Id> },
     Ioverb { fd: u8 },
     Int { value: i64 },
     Float { value: f64 },
+    Temporal { kind: Temporal, value: i64 },
     Lambda { args: Args, body: Id },
     Native { name: u16 },
     List {
@@ -173,6 +178,12 @@ pub enum AST {
         keys: Vector<AST, Id>,
         values: Vector<AST, Id>,
     },
+    Table {
+        columns: Vector<AST, Id>,
+        values: Vector<AST, Id>,
+    },
+    Mapped { id: u32 },
+    Partitioned { id: u32 },
     Nameref { name: u16, value: Id },
     Adverb {
         kind: Adverb,
@@ -208,18 +219,23 @@ impl AST {
 
     pub fn type_id(&self) -> i8 {
         match *self {
+            AST::Byte { .. } => -4,
             AST::Int { .. } => -7,
             AST::Float { .. } => -8,
             AST::Symbol { .. } => -9,
+            AST::Temporal { kind: k, .. } => k.type_id(),
+            AST::Table { .. } | AST::Partitioned { .. } => 98,
             _ => !0 as i8,
         }
     }
 
     pub fn is_atom(&self) -> bool {
         match *self {
+            AST::Byte { .. } => true,
             AST::Int { .. } => true,
             AST::Float { .. } => true,
             AST::Symbol { .. } => true,
+            AST::Temporal { .. } => true,
             _ => false,
         }
     }
@@ -241,10 +257,15 @@ impl<'a> fmt::Display for Land<'a, AST> {
         match *ast {
             AST::Name { value: v } => write!(f, "{}", arena.id_name(v)),
             AST::Bool { value: v } => write!(f, "{}b", v as u8),
+            AST::Byte { value: v } => write!(f, "0x{:02x}", v),
             AST::Symbol { value: v } => write!(f, "`{}", arena.id_symbol(v)),
             AST::String { value: ref s } => write!(f, "\"{}\"", s.to_string()),
+            AST::Text { .. } => write!(f, "\"{}\"", arena.string_of(ast).unwrap_or(String::new())),
+            AST::Int { value: NULL } => write!(f, "0N"),
             AST::Int { value: v } => write!(f, "{}", v),
-            AST::Float { value: v } => write!(f, "{}", v),
+            AST::Float { value: v } => write!(f, "{}", float(v, arena.console.precision)),
+            AST::Temporal { kind: k, value: v } => write!(f, "{}", temporal::format(k, v)),
+            AST::Ioverb { fd: d } => write!(f, "{}:", d),
             AST::Verb { kind: ref v, args: ref a } => {
                 let s = &a.as_slice(&arena.ast);
                 if s.len() > 0 {
@@ -269,6 +290,14 @@ impl<'a> fmt::Display for Land<'a, AST> {
                     Ok(())
                 } else if v.len() == 1 {
                     write!(f, ",{}", Land(v.get(0, &arena.ast), arena))
+                } else if is_bytes(&arena.ast, v) {
+                    try!(write!(f, "0x"));
+                    for x in v.iter(&arena.ast) {
+                        if let AST::Byte { value: b } = *x {
+                            try!(write!(f, "{:02x}", b));
+                        }
+                    }
+                    Ok(())
                 } else {
                     if is_unified(&arena.ast, v) {
                         for i in 0..v.len() - 1 {
@@ -291,6 +320,9 @@ impl<'a> fmt::Display for Land<'a, AST> {
                     }
                 }
             }
+            AST::Dict { keys: ref k, values: ref v } if k.len() == 0 || v.len() == 0 => {
+                write!(f, "[]")
+            }
             AST::Dict { keys: ref k, values: ref v } => {
                 try!(write!(f, "["));
                 let u = k.as_slice(&arena.ast);
@@ -303,6 +335,39 @@ impl<'a> fmt::Display for Land<'a, AST> {
                        Land(&u[u.len() - 1], arena),
                        Land(&m[m.len() - 1], arena))
             }
+            AST::Table { columns: ref c, values: ref v } => write_table(f, c, v, arena),
+            AST::Partitioned { id: n } => {
+                // the dates only, partitions are not opened for display
+                let p = &arena.partitioned[n as usize];
+                let mut s = format!("{} by date:", p.table);
+                for d in &p.dates {
+                    s.push_str(&format!(" {}", temporal::format(Temporal::Date, *d)));
+                }
+                if arena.console.cols > 2 && s.chars().count() > arena.console.cols {
+                    s = s.chars().take(arena.console.cols - 2).collect::<String>() + "..";
+                }
+                write!(f, "{}", s)
+            }
+            AST::Mapped { id: n } => {
+                let m = arena.column(n);
+                if m.len == 1 {
+                    return write!(f, ",{}", Land(&m.get(0), arena));
+                }
+                if let Elem::Byte = m.elem {
+                    try!(write!(f, "0x"));
+                    for i in 0..m.len {
+                        if let AST::Byte { value: b } = m.get(i) {
+                            try!(write!(f, "{:02x}", b));
+                        }
+                    }
+                    return Ok(());
+                }
+                for i in 0..m.len {
+                    let s = if i + 1 < m.len { " " } else { "" };
+                    try!(write!(f, "{}{}", Land(&m.get(i), arena), s));
+                }
+                Ok(())
+            }
             AST::Condition { list: ref c } => {
                 let l = c.as_slice(&arena.ast);
                 let _ = write!(f, "$[");
@@ -327,10 +392,171 @@ impl<'a> fmt::Display for Land<'a, AST> {
     }
 }
 
+// Null of integers and temporals, shown as 0N.
+pub const NULL: i64 = ::std::i64::MIN;
+
+// Header, a rule and one line per row, symbols without their quote. Only the
+// rows the console shows are measured.
+fn write_table(f: &mut fmt::Formatter,
+               columns: &Vector<AST, Id>,
+               values: &Vector<AST, Id>,
+               arena: &Arena)
+               -> fmt::Result {
+    let cell = |x: &AST| match *x {
+        AST::Symbol { value: v } => arena.id_symbol(v),
+        _ => format!("{}", Land(x, arena)),
+    };
+    let mut cols: Vec<Vec<String>> = Vec::new();
+    let mut rows = 0;
+    for (c, v) in columns.iter(&arena.ast).zip(values.iter(&arena.ast)) {
+        let mut col = vec![cell(c)];
+        if let Some(len) = count(v, arena) {
+            let n = match arena.console.rows {
+                0 => len,
+                r => len.min(r),
+            };
+            rows = n;
+            col.extend((0..n).filter_map(|i| item(v, i, arena)).map(|y| cell(&y)));
+        }
+        cols.push(col);
+    }
+    let widths: Vec<usize> = cols.iter()
+        .map(|c| c.iter().map(|s| s.chars().count()).max().unwrap_or(0))
+        .collect();
+    let line = |i: usize| {
+        let mut s = String::new();
+        for (c, w) in cols.iter().zip(&widths) {
+            let x = c.get(i).map(|x| &x[..]).unwrap_or("");
+            s.push_str(&format!("{:<1$} ", x, w));
+        }
+        s.trim_end().to_string()
+    };
+    try!(write!(f, "{}\n", line(0)));
+    let w = widths.iter().fold(0, |a, x| a + x + 1);
+    try!(write!(f, "{}", "-".repeat(if w > 0 { w - 1 } else { 0 })));
+    for i in 0..rows {
+        try!(write!(f, "\n{}", line(i + 1)));
+    }
+    Ok(())
+}
+
+// Console size and float display precision, zero means no limit.
+#[derive(Debug, Clone, Copy)]
+pub struct Console {
+    pub rows: usize,
+    pub cols: usize,
+    pub precision: usize,
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console {
+            rows: 25,
+            cols: 80,
+            precision: 7,
+        }
+    }
+}
+
+// Writer which gives up once it holds more than the console can show,
+// so that huge values are never formatted in full. A max of 0 bytes or
+// rows is unlimited. Past cols a line is not kept beyond the character
+// that shows it is longer, 0 cols is unlimited.
+struct Screen {
+    buf: String,
+    max: usize,
+    rows: usize,
+    lines: usize,
+    cols: usize,
+    col: usize,
+}
+
+impl fmt::Write for Screen {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.max > 0 && self.buf.len() >= self.max {
+                return Err(fmt::Error);
+            }
+            if self.rows > 0 && self.lines >= self.rows {
+                return Err(fmt::Error);
+            }
+            if c == '\n' {
+                self.lines += 1;
+                self.col = 0;
+            } else if self.cols > 0 && self.col > self.cols {
+                continue;
+            } else {
+                self.col += 1;
+            }
+            self.buf.push(c);
+        }
+        Ok(())
+    }
+}
+
+fn float(v: f64, precision: usize) -> String {
+    if v.is_nan() {
+        return "0n".to_string();
+    }
+    if v.is_infinite() {
+        return if v > 0.0 { "0w" } else { "-0w" }.to_string();
+    }
+    if precision == 0 || v == 0.0 {
+        return format!("{}", v);
+    }
+    // precision is the number of significant digits
+    let digits = v.abs().log10().floor() as i64 + 1;
+    let decimals = if (precision as i64) > digits {
+        (precision as i64 - digits) as usize
+    } else {
+        0
+    };
+    let s = format!("{:.*}", decimals, v);
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        s
+    }
+}
+
+fn clip(l: &str, cols: usize) -> String {
+    if cols == 0 || l.chars().count() <= cols {
+        return l.to_string();
+    }
+    let t: String = l.chars().take(if cols > 2 { cols - 2 } else { 0 }).collect();
+    t + ".."
+}
+
+pub fn format(ast: &AST, arena: &Arena) -> String {
+    let c = arena.console;
+    // 0 rows or columns leaves that dimension unlimited
+    let mut screen = Screen {
+        buf: String::new(),
+        max: if c.cols > 0 { c.rows * (c.cols + 1) } else { 0 },
+        rows: c.rows,
+        lines: 0,
+        cols: c.cols,
+        col: 0,
+    };
+    let mut more = fmt::write(&mut screen, format_args!("{}", Land(ast, arena))).is_err();
+    let mut lines: Vec<String> = screen.buf.lines().map(|l| l.to_string()).collect();
+    if c.rows > 0 && lines.len() > c.rows {
+        lines.truncate(c.rows);
+        more = true;
+    }
+    if more {
+        if let Some(l) = lines.last_mut() {
+            l.push_str("..");
+        }
+    }
+    let r: Vec<String> = lines.iter().map(|l| clip(l, c.cols)).collect();
+    r.join("\n")
+}
+
 pub fn print(ast: &AST, arena: &Arena) {
     match ast {
         &AST::Nil => (),
-        a => println!("{}", Land(a, arena)),
+        a => println!("{}", format(a, arena)),
     }
 }
 
@@ -359,10 +585,10 @@ pub fn vector(arena: &mut ArenaMem<AST, Id>, mut v: Vec<AST>) -> Vector<AST, Id>
     vec
 }
 
-pub fn list(curry: bool, arena: &mut ArenaMem<AST, Id>, mut v: Vec<AST>) -> AST {
+pub fn list(curry: bool, arena: &mut ArenaMem<AST, Id>, v: Vec<AST>) -> AST {
     let vec = arena.alloc_vec::<AST>(v.len());
-    for u in vec.as_slice_mut(arena) {
-        *u = v.remove(0);
+    for (u, x) in vec.as_slice_mut(arena).iter_mut().zip(v) {
+        *u = x;
     }
     AST::List {
         curry: curry,
@@ -412,10 +638,45 @@ pub fn dict(arena: &mut ArenaMem<AST, Id>, mut keys: Vec<AST>, mut values: Vec<A
     }
 }
 
+pub fn table(arena: &mut ArenaMem<AST, Id>, columns: Vec<AST>, values: Vec<AST>) -> AST {
+    AST::Table {
+        columns: vector(arena, columns),
+        values: vector(arena, values),
+    }
+}
+
 pub fn atom(arena: &mut ArenaMem<AST, Id>, ast: AST) -> Id {
     arena.push(ast)
 }
 
+// Length of a list or a mapped column.
+pub fn count(ast: &AST, arena: &Arena) -> Option<usize> {
+    match *ast {
+        AST::List { curry: _, values: ref v } => Some(v.len()),
+        AST::Mapped { id: n } => Some(arena.column(n).len),
+        _ => None,
+    }
+}
+
+pub fn item(ast: &AST, i: usize, arena: &Arena) -> Option<AST> {
+    match *ast {
+        AST::List { curry: _, values: ref v } if i < v.len() => Some(*v.get(i, &arena.ast)),
+        AST::Mapped { id: n } if i < arena.column(n).len => {
+            Some(arena.column(n).get(i))
+        }
+        _ => None,
+    }
+}
+
+// Elements of a list or a mapped column.
+pub fn items(ast: &AST, arena: &Arena) -> Option<Vec<AST>> {
+    match *ast {
+        AST::List { curry: _, values: ref v } => Some(v.iter(&arena.ast).map(|x| *x).collect()),
+        AST::Mapped { id: n } => Some(arena.column(n).items()),
+        _ => None,
+    }
+}
+
 pub fn is_unified(arena: &ArenaMem<AST, Id>, vec: &Vector<AST, Id>) -> bool {
     let mut it = vec.iter(arena);
     let o = it.next();
@@ -430,6 +691,13 @@ pub fn is_unified(arena: &ArenaMem<AST, Id>, vec: &Vector<AST, Id>) -> bool {
     false
 }
 
+pub fn is_bytes(arena: &ArenaMem<AST, Id>, vec: &Vector<AST, Id>) -> bool {
+    vec.iter(arena).all(|x| match *x {
+        AST::Byte { .. } => true,
+        _ => false,
+    })
+}
+
 pub fn is_flat(arena: &ArenaMem<AST, Id>, vec: &Vector<AST, Id>) -> bool {
     for x in vec.iter(arena) {
         if !x.is_atom() {
@@ -0,0 +1,141 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::ptr;
+use std::slice;
+use libc;
+use parse::ast::AST;
+use parse::temporal::Temporal;
+
+// Element of a mapped column, all little endian. Symbols are u32 indices
+// into the enumeration domain of the column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Elem {
+    Bool,
+    Byte,
+    Int,
+    Float,
+    Temporal(Temporal),
+    Symbol,
+}
+
+impl Elem {
+    pub fn width(&self) -> usize {
+        match *self {
+            Elem::Bool | Elem::Byte => 1,
+            Elem::Symbol => 4,
+            _ => 8,
+        }
+    }
+}
+
+// Read only map of a column file whose elements start at offset. Elements are
+// decoded on access, nothing is copied into the arena until arithmetic needs
+// the column as a list, which is then kept in list.
+pub struct Mapped {
+    ptr: *mut libc::c_void,
+    size: usize,
+    offset: usize,
+    pub elem: Elem,
+    pub len: usize,
+    pub domain: Vec<u16>,
+    pub list: Option<AST>,
+}
+
+impl Mapped {
+    pub fn open(path: &str,
+                offset: usize,
+                elem: Elem,
+                len: usize,
+                domain: Vec<u16>)
+                -> io::Result<Mapped> {
+        let f = try!(File::open(path));
+        let available = try!(f.metadata()).len();
+        let size = match len.checked_mul(elem.width()).and_then(|n| n.checked_add(offset)) {
+            Some(n) if n as u64 <= available => n,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "short column")),
+        };
+        let ptr = if size == 0 {
+            ptr::null_mut()
+        } else {
+            let p = unsafe {
+                libc::mmap(ptr::null_mut(),
+                           size,
+                           libc::PROT_READ,
+                           libc::MAP_PRIVATE,
+                           f.as_raw_fd(),
+                           0)
+            };
+            if p == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            p
+        };
+        Ok(Mapped {
+            ptr: ptr,
+            size: size,
+            offset: offset,
+            elem: elem,
+            len: len,
+            domain: domain,
+            list: None,
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        if self.size == 0 {
+            return &[];
+        }
+        unsafe { &slice::from_raw_parts(self.ptr as *const u8, self.size)[self.offset..] }
+    }
+
+    // Undecoded element i, callers check the length.
+    fn raw(&self, i: usize) -> u64 {
+        let w = self.elem.width();
+        let b = &self.bytes()[i * w..(i + 1) * w];
+        b.iter().rev().fold(0u64, |a, &c| (a << 8) | c as u64)
+    }
+
+    // Element i, callers check the length. The header of a symbol column
+    // holds the domain size it was written with, checked when it is opened.
+    pub fn get(&self, i: usize) -> AST {
+        let x = self.raw(i);
+        match self.elem {
+            Elem::Bool => AST::Bool { value: x != 0 },
+            Elem::Byte => AST::Byte { value: x as u8 },
+            Elem::Int => AST::Int { value: x as i64 },
+            Elem::Float => AST::Float { value: f64::from_bits(x) },
+            Elem::Temporal(k) => {
+                AST::Temporal {
+                    kind: k,
+                    value: x as i64,
+                }
+            }
+            Elem::Symbol => AST::Symbol { value: self.domain[x as usize] },
+        }
+    }
+
+    pub fn items(&self) -> Vec<AST> {
+        (0..self.len).map(|i| self.get(i)).collect()
+    }
+}
+
+impl Drop for Mapped {
+    fn drop(&mut self) {
+        if self.size > 0 {
+            unsafe {
+                libc::munmap(self.ptr, self.size);
+            }
+        }
+    }
+}
+
+// Table split by date into splayed tables, root/2024.01.31/table, in date
+// order. A partition is opened the first time it is used.
+pub struct Partitioned {
+    pub root: String,
+    pub table: String,
+    pub dates: Vec<i64>,
+    pub dirs: Vec<String>,
+    pub loaded: Vec<Option<AST>>,
+}
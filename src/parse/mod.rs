@@ -6,3 +6,6 @@ pub mod vector;
 pub mod alloc;
 pub mod arena;
 pub mod parser;
+pub mod temporal;
+pub mod mapped;
+pub mod syntax;
@@ -4,9 +4,13 @@ use parse::ast::{self, AST, Args};
 use parse::token::{Token, Raw};
 use regex::Regex;
 use parse::alloc::Arena;
+use parse::temporal;
 
 pub struct Parser {
     text: String,
+    // Tokens taken from the text, compared with token::tokenize in tests.
+    #[cfg(test)]
+    taken: Vec<(Token, String)>,
 }
 
 macro_rules! extract {
@@ -19,7 +23,7 @@ macro_rules! extract {
 impl Parser {
     fn begin(&mut self, s: &str) {
         lazy_static! {
-            static ref RE: Regex = Regex::new(r"(\x22(?:[^\x22\x5C\n]|\.)*\x22)|[a-zA-Z]*[ ]+(/.*)|([a-z\d\]\)]-\.?\d+)|.")
+            static ref RE: Regex = Regex::new(r"(\x22(?:[^\x22\x5C\n]|\x5C.)*\x22)|[a-zA-Z]*[ ]+(/.*)|([a-z\d\]\)]-\.?\d+)|.")
                 .unwrap();
         }
         // preserve a string, remove a comment, disambiguate a minus sign.
@@ -62,6 +66,8 @@ impl Parser {
         match t.find(&self.text) {
             Some(x) => {
                 self.text = self.text[x.len()..].trim_left().to_string();
+                #[cfg(test)]
+                self.taken.push((t, x.value().to_string()));
                 Ok(x)
             }
             None => Err(Error::ParseError(format!("Expected: {:?}", t))),
@@ -91,9 +97,9 @@ impl Parser {
 
     #[inline]
     fn at_noun(&self) -> bool {
-        !self.done() && self.at(Token::Number) || self.at(Token::Name) ||
-        self.at(Token::Symbol) || self.at(Token::String) || self.at(Token::Cond) ||
-        self.at(Token::OpenP) || self.at(Token::OpenC)
+        !self.done() && self.at(Token::Number) || self.at(Token::Temporal) ||
+        self.at(Token::Name) || self.at(Token::Symbol) || self.at(Token::String) ||
+        self.at(Token::Cond) || self.at(Token::OpenP) || self.at(Token::OpenC)
     }
 
     fn applycallright(&mut self, arena: &mut Arena, node: AST) -> Result<AST, Error> {
@@ -151,6 +157,30 @@ impl Parser {
                 body: ast::atom(&mut arena.ast, b),
             });
         }
+        if self.at(Token::Temporal) {
+            let mut v: Vec<AST> = Vec::new();
+            while self.at(Token::Temporal) {
+                let n = try!(self.expect(Token::Temporal));
+                match temporal::parse(n.value()) {
+                    Some((k, x)) => {
+                        v.push(AST::Temporal {
+                            kind: k,
+                            value: x,
+                        })
+                    }
+                    None => {
+                        return Err(Error::ParseError(format!("Malformed temporal {}.", n.value())))
+                    }
+                }
+            }
+            return match v.len() {
+                1 => self.applyindexright(arena, v.pop().unwrap()),
+                _ => {
+                    let list = ast::list(true, &mut arena.ast, v);
+                    self.applyindexright(arena, list)
+                }
+            };
+        }
         if self.at(Token::Ioverb) {
             let v = try!(self.expect(Token::Ioverb));
             return match v.value()[..1].parse::<u8>() {
@@ -169,9 +199,22 @@ impl Parser {
         }
         if self.at(Token::Hexlit) {
             let h = try!(self.expect(Token::Hexlit));
-            return match i64::from_str_radix(&h.value()[2..], 16) {
-                Ok(v) => Ok(AST::Int { value: v }),
-                Err(_) => Err(Error::ParseError(format!("Malformed byte string."))),
+            // an odd digit count is padded on the left: 0x1 is 0x01
+            let d = &h.value()[2..];
+            let s = if d.len() % 2 == 1 { format!("0{}", d) } else { d.to_string() };
+            let mut v: Vec<AST> = Vec::new();
+            for i in 0..s.len() / 2 {
+                match u8::from_str_radix(&s[2 * i..2 * i + 2], 16) {
+                    Ok(b) => v.push(AST::Byte { value: b }),
+                    Err(_) => return Err(Error::ParseError(format!("Malformed byte string."))),
+                }
+            }
+            return match v.len() {
+                1 => self.applyindexright(arena, v.pop().unwrap()),
+                _ => {
+                    let list = ast::list(true, &mut arena.ast, v);
+                    self.applyindexright(arena, list)
+                }
             };
         }
         if self.matches(Token::Cond).is_some() {
@@ -182,9 +225,24 @@ impl Parser {
         }
         if self.at(Token::Number) {
             let mut v: Vec<AST> = Vec::new();
-            while self.at(Token::Number) {
+            while self.at(Token::Number) && !self.at(Token::Ioverb) {
                 let mut n = try!(self.expect(Token::Number));
                 n = n.trim_right_matches(|c| c == 'i' || c == 'f');
+                match n.value() {
+                    "0N" | "-0N" => {
+                        v.push(AST::Int { value: ast::NULL });
+                        continue;
+                    }
+                    "0w" => {
+                        v.push(AST::Float { value: f64::INFINITY });
+                        continue;
+                    }
+                    "-0w" => {
+                        v.push(AST::Float { value: f64::NEG_INFINITY });
+                        continue;
+                    }
+                    _ => (),
+                }
                 match n.parse::<i64>() {
                     Ok(x) => v.push(AST::Int { value: x }),
                     Err(_) => {
@@ -242,10 +300,8 @@ impl Parser {
             let mut t = try!(s.parse::<String>());
             t.remove(0);
             t.pop();
-            if let Some(v) = ast::Chars::new(&mut t) {
-                return self.applyindexright(arena, AST::String { value: v });
-            }
-            return Err(Error::StringSize);
+            let v = arena.string(unescape(&t));
+            return self.applyindexright(arena, v);
         }
         if self.at(Token::Name) {
             let n = try!(self.expect(Token::Name));
@@ -376,6 +432,14 @@ impl Parser {
                 x => Ok(ast::verb(&mut arena.ast, '@', vec![x, p])),
             };
         }
+        if self.at(Token::Ioverb) {
+            // dyadic ioverb: "file" 0: lines
+            let io = try!(self.parse_noun(arena));
+            let x = try!(self.parse_noun(arena));
+            let r = try!(self.parse_ex(arena, x));
+            let args = ast::list(true, &mut arena.ast, vec![node, r]);
+            return Ok(ast::verb(&mut arena.ast, '@', vec![io, args]));
+        }
         if self.at(Token::Verb) {
             let n = try!(self.expect(Token::Verb));
             let v = n.value();
@@ -420,6 +484,59 @@ impl Parser {
     }
 }
 
+// Resolves the escapes of a string literal: \" \\ \n \r \t.
+fn unescape(s: &str) -> String {
+    let mut r = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            r.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => r.push('\n'),
+            Some('r') => r.push('\r'),
+            Some('t') => r.push('\t'),
+            Some(e) => r.push(e),
+            None => r.push(c),
+        }
+    }
+    r
+}
+
 pub fn new() -> Parser {
-    Parser { text: String::new() }
+    Parser {
+        text: String::new(),
+        #[cfg(test)]
+        taken: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Read;
+    use parse::token;
+
+    // Editor tooling splits the expressions of basic.k into the tokens the
+    // parser takes, comments aside.
+    #[test]
+    fn tokenize() {
+        let mut text = String::new();
+        File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/k/basic.k"))
+            .and_then(|mut f| f.read_to_string(&mut text))
+            .unwrap();
+        for l in text.lines().filter(|l| !l.is_empty() && !l.starts_with('/')) {
+            let mut p = new();
+            let mut arena = Arena::new();
+            p.parse_str(l, &mut arena).unwrap();
+            let lexed: Vec<(Token, String)> = token::tokenize(l)
+                .iter()
+                .filter(|x| x.token != Token::Comment)
+                .map(|x| (x.token, x.text(l).to_string()))
+                .collect();
+            assert_eq!(p.taken, lexed, "{}", l);
+        }
+    }
 }
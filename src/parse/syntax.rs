@@ -0,0 +1,296 @@
+use parse::token::{self, Lexeme, Span, Token};
+
+// Syntax tree over raw source for editor tooling. Every token of the source is
+// a leaf, so flattening the tree gives back the token stream of token::tokenize.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Program,
+    Statement,
+    Lambda,
+    Paren,
+    Bracket,
+    Cond,
+    Assign,
+    Leaf(Token),
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub kind: Kind,
+    pub span: Span,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn leaf(l: &Lexeme) -> Node {
+        Node {
+            kind: Kind::Leaf(l.token),
+            span: l.span,
+            children: vec![],
+        }
+    }
+
+    fn branch(kind: Kind, children: Vec<Node>) -> Node {
+        let span = match (children.first(), children.last()) {
+            (Some(a), Some(b)) => {
+                Span {
+                    start: a.span.start,
+                    end: b.span.end,
+                }
+            }
+            _ => Span { start: 0, end: 0 },
+        };
+        Node {
+            kind: kind,
+            span: span,
+            children: children,
+        }
+    }
+
+    pub fn is_block(&self) -> bool {
+        match self.kind {
+            Kind::Lambda | Kind::Paren | Kind::Bracket | Kind::Cond => true,
+            _ => false,
+        }
+    }
+
+    pub fn leaves(&self) -> Vec<&Node> {
+        match self.kind {
+            Kind::Leaf(_) => vec![self],
+            _ => self.children.iter().flat_map(|c| c.leaves()).collect(),
+        }
+    }
+
+    // Innermost node chain containing offset, outermost first.
+    pub fn path(&self, offset: usize) -> Vec<&Node> {
+        let mut r = vec![self];
+        let mut n = self;
+        while let Some(c) = n.children.iter().find(|c| c.span.contains(offset)) {
+            r.push(c);
+            n = c;
+        }
+        r
+    }
+
+    // Closing bracket leaf of a block, None if the block is left open.
+    fn closer(&self) -> Option<&Node> {
+        if !self.is_block() || self.children.len() < 2 {
+            return None;
+        }
+        let c = self.children.last().unwrap();
+        match c.kind {
+            Kind::Leaf(Token::CloseB) |
+            Kind::Leaf(Token::CloseP) |
+            Kind::Leaf(Token::CloseC) => Some(c),
+            _ => None,
+        }
+    }
+
+    // Span of the bracket matching the one at offset.
+    pub fn matching(&self, offset: usize) -> Option<Span> {
+        for n in self.path(offset).iter().rev() {
+            if let Some(close) = n.closer() {
+                let open = &n.children[0];
+                if open.span.contains(offset) {
+                    return Some(close.span);
+                }
+                if close.span.contains(offset) {
+                    return Some(open.span);
+                }
+            }
+        }
+        None
+    }
+
+    // Span of the name assigned or declared as a lambda argument for the name
+    // at offset, looking in enclosing lambdas first.
+    pub fn definition(&self, s: &str, offset: usize) -> Option<Span> {
+        let path = self.path(offset);
+        let name = match path.last() {
+            Some(n) if n.kind == Kind::Leaf(Token::Name) => &s[n.span.start..n.span.end],
+            _ => return None,
+        };
+        for scope in path.iter().rev() {
+            if scope.kind != Kind::Lambda && scope.kind != Kind::Program {
+                continue;
+            }
+            if let Some(x) = scope.defines(s, name) {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    fn defines(&self, s: &str, name: &str) -> Option<Span> {
+        if self.kind == Kind::Lambda {
+            // {[a;b] ...} the argument list opens the first statement
+            if let Some(args) = self.children.get(1).and_then(|x| x.children.first()) {
+                if args.kind == Kind::Bracket {
+                    let d = args.leaves()
+                        .into_iter()
+                        .find(|l| {
+                            l.kind == Kind::Leaf(Token::Name) &&
+                            &s[l.span.start..l.span.end] == name
+                        });
+                    if let Some(l) = d {
+                        return Some(l.span);
+                    }
+                }
+            }
+        }
+        for c in self.children.iter() {
+            if c.kind == Kind::Lambda {
+                continue;
+            }
+            if c.kind == Kind::Assign {
+                let n = &c.children[0];
+                if &s[n.span.start..n.span.end] == name {
+                    return Some(n.span);
+                }
+            }
+            if let Some(x) = c.defines(s, name) {
+                return Some(x);
+            }
+        }
+        None
+    }
+}
+
+fn close(t: Token) -> Option<(Kind, Token)> {
+    match t {
+        Token::OpenC => Some((Kind::Lambda, Token::CloseC)),
+        Token::OpenP => Some((Kind::Paren, Token::CloseP)),
+        Token::OpenB => Some((Kind::Bracket, Token::CloseB)),
+        Token::Cond => Some((Kind::Cond, Token::CloseB)),
+        _ => None,
+    }
+}
+
+// name: value and name+: value become an Assign node covering the rest of the statement.
+fn assigns(mut items: Vec<Node>) -> Vec<Node> {
+    for i in 0..items.len() {
+        if i + 1 < items.len() && items[i].kind == Kind::Leaf(Token::Name) &&
+           (items[i + 1].kind == Kind::Leaf(Token::Colon) ||
+            items[i + 1].kind == Kind::Leaf(Token::Assign)) {
+            let rest = items.split_off(i + 2);
+            let mut a = items.split_off(i);
+            a.extend(assigns(rest));
+            items.push(Node::branch(Kind::Assign, a));
+            return items;
+        }
+    }
+    items
+}
+
+fn statements(l: &[Lexeme], pos: &mut usize, end: Option<Token>) -> Vec<Node> {
+    let mut r: Vec<Node> = Vec::new();
+    let mut items: Vec<Node> = Vec::new();
+    while *pos < l.len() {
+        let t = l[*pos].token;
+        if Some(t) == end {
+            break;
+        }
+        if t == Token::Semi {
+            if !items.is_empty() {
+                r.push(Node::branch(Kind::Statement, assigns(items)));
+                items = Vec::new();
+            }
+            r.push(Node::leaf(&l[*pos]));
+            *pos += 1;
+            continue;
+        }
+        match close(t) {
+            Some((kind, c)) => {
+                let mut block = vec![Node::leaf(&l[*pos])];
+                *pos += 1;
+                block.extend(statements(l, pos, Some(c)));
+                if *pos < l.len() {
+                    block.push(Node::leaf(&l[*pos]));
+                    *pos += 1;
+                }
+                items.push(Node::branch(kind, block));
+            }
+            None => {
+                items.push(Node::leaf(&l[*pos]));
+                *pos += 1;
+            }
+        }
+    }
+    if !items.is_empty() {
+        r.push(Node::branch(Kind::Statement, assigns(items)));
+    }
+    r
+}
+
+pub fn parse(s: &str) -> Node {
+    let l = token::tokenize(s);
+    let mut pos = 0;
+    // unmatched closing brackets end up as plain leaves
+    let mut n = Node::branch(Kind::Program, statements(&l, &mut pos, None));
+    n.span = Span {
+        start: 0,
+        end: s.len(),
+    };
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Span of the n-th occurrence of the text at in s.
+    fn at(s: &str, at: &str, n: usize) -> Span {
+        let start = s.match_indices(at).nth(n).unwrap().0;
+        Span {
+            start: start,
+            end: start + at.len(),
+        }
+    }
+
+    #[test]
+    fn matching_brackets() {
+        let s = "f:{[x](x+1;g[x;(2)]);$[x;\"(]\";1]}";
+        let n = parse(s);
+        assert_eq!(n.matching(at(s, "{", 0).start), Some(at(s, "}", 0)));
+        assert_eq!(n.matching(at(s, "}", 0).start), Some(at(s, "{", 0)));
+        assert_eq!(n.matching(at(s, "[", 0).start), Some(at(s, "]", 0)));
+        assert_eq!(n.matching(at(s, "(", 0).start), Some(at(s, ")", 1)));
+        assert_eq!(n.matching(at(s, "(", 1).start), Some(at(s, ")", 0)));
+        assert_eq!(n.matching(at(s, "[", 1).start), Some(at(s, "]", 1)));
+        assert_eq!(n.matching(at(s, "$[", 0).start), Some(at(s, "]", 3)));
+        // brackets in a string are text
+        assert_eq!(n.matching(at(s, "(", 2).start), None);
+        assert_eq!(n.matching(at(s, "x", 0).start), None);
+    }
+
+    #[test]
+    fn unclosed_brackets() {
+        let s = "{x+(1";
+        let n = parse(s);
+        assert_eq!(n.matching(0), None);
+        assert_eq!(n.matching(3), None);
+        let s = "1)+(2)";
+        let n = parse(s);
+        assert_eq!(n.matching(1), None);
+        assert_eq!(n.matching(3), Some(at(s, ")", 1)));
+    }
+
+    #[test]
+    fn definitions() {
+        let s = "a:1;x:2;f:{[x;y]z:x+a;{[a]a*z}[z*y]};\"x\"";
+        let n = parse(s);
+        // arguments shadow globals
+        assert_eq!(n.definition(s, at(s, "x", 2).start), Some(at(s, "x", 1)));
+        assert_eq!(n.definition(s, at(s, "y", 1).start), Some(at(s, "y", 0)));
+        assert_eq!(n.definition(s, at(s, "a", 1).start), Some(at(s, "a", 0)));
+        assert_eq!(n.definition(s, at(s, "z", 2).start), Some(at(s, "z", 0)));
+        // the inner lambda has its own a, z comes from the outer one
+        assert_eq!(n.definition(s, at(s, "a", 3).start), Some(at(s, "a", 2)));
+        assert_eq!(n.definition(s, at(s, "z", 1).start), Some(at(s, "z", 0)));
+        assert_eq!(n.definition(s, at(s, "f", 0).start), Some(at(s, "f", 0)));
+        // neither names nor defined
+        assert_eq!(n.definition(s, s.len() - 2), None);
+        assert_eq!(n.definition(s, at(s, "1", 0).start), None);
+        assert_eq!(parse("g 1").definition("g 1", 0), None);
+    }
+}
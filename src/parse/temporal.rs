@@ -0,0 +1,327 @@
+use std::fmt;
+use parse::ast::{self, AST};
+
+pub const NS_SECOND: i64 = 1000000000;
+pub const NS_MINUTE: i64 = 60 * NS_SECOND;
+pub const NS_HOUR: i64 = 60 * NS_MINUTE;
+pub const NS_DAY: i64 = 24 * NS_HOUR;
+
+// Days between 1970.01.01 and 2000.01.01, K epoch is the latter.
+pub const EPOCH: i64 = 10957;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Temporal {
+    Date, // days since 2000.01.01
+    Minute, // minutes since midnight
+    Second, // seconds since midnight
+    Time, // milliseconds since midnight
+    Timestamp, // nanoseconds since 2000.01.01D00:00
+    Timespan, // nanoseconds
+}
+
+impl Temporal {
+    pub fn type_id(&self) -> i8 {
+        match *self {
+            Temporal::Timestamp => -12,
+            Temporal::Date => -14,
+            Temporal::Timespan => -16,
+            Temporal::Minute => -17,
+            Temporal::Second => -18,
+            Temporal::Time => -19,
+        }
+    }
+
+    pub fn from_type_id(t: i8) -> Option<Temporal> {
+        match t {
+            -12 => Some(Temporal::Timestamp),
+            -14 => Some(Temporal::Date),
+            -16 => Some(Temporal::Timespan),
+            -17 => Some(Temporal::Minute),
+            -18 => Some(Temporal::Second),
+            -19 => Some(Temporal::Time),
+            _ => None,
+        }
+    }
+
+    // Nanoseconds in one unit of the value.
+    fn unit(&self) -> i64 {
+        match *self {
+            Temporal::Date => NS_DAY,
+            Temporal::Minute => NS_MINUTE,
+            Temporal::Second => NS_SECOND,
+            Temporal::Time => 1000000,
+            Temporal::Timestamp => 1,
+            Temporal::Timespan => 1,
+        }
+    }
+}
+
+impl fmt::Display for Temporal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Temporal::Date => write!(f, "{}", "date"),
+            Temporal::Minute => write!(f, "{}", "minute"),
+            Temporal::Second => write!(f, "{}", "second"),
+            Temporal::Time => write!(f, "{}", "time"),
+            Temporal::Timestamp => write!(f, "{}", "timestamp"),
+            Temporal::Timespan => write!(f, "{}", "timespan"),
+        }
+    }
+}
+
+fn div(a: i64, b: i64) -> i64 {
+    let d = a / b;
+    if (a % b != 0) && ((a < 0) != (b < 0)) {
+        d - 1
+    } else {
+        d
+    }
+}
+
+fn rem(a: i64, b: i64) -> i64 {
+    a - div(a, b) * b
+}
+
+// Proleptic gregorian calendar, see http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = div(y, 400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468 - EPOCH
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + EPOCH + 719468;
+    let era = div(z, 146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (if m <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, m, d)
+}
+
+fn date(s: &str) -> Option<i64> {
+    let p: Vec<&str> = s.split('.').collect();
+    if p.len() != 3 {
+        return None;
+    }
+    match (p[0].parse::<i64>(), p[1].parse::<i64>(), p[2].parse::<i64>()) {
+        (Ok(y), Ok(m), Ok(d)) if m >= 1 && m <= 12 && d >= 1 && d <= 31 => {
+            Some(days_from_civil(y, m, d))
+        }
+        _ => None,
+    }
+}
+
+// Parses hh:mm[:ss[.fff]] into number of fields and nanoseconds since midnight.
+fn clock(s: &str) -> Option<(usize, i64)> {
+    let (hms, frac) = match s.find('.') {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let mut ns = 0;
+    let mut n = 0;
+    for (p, unit) in hms.split(':').zip(&[NS_HOUR, NS_MINUTE, NS_SECOND]) {
+        match p.parse::<i64>() {
+            Ok(x) => ns += x * unit,
+            Err(_) => return None,
+        }
+        n += 1;
+    }
+    if let Some(f) = frac {
+        if n != 3 || f.len() > 9 {
+            return None;
+        }
+        let mut digits = f.to_string();
+        while digits.len() < 9 {
+            digits.push('0');
+        }
+        match digits.parse::<i64>() {
+            Ok(x) => ns += x,
+            Err(_) => return None,
+        }
+        n += 1;
+    }
+    Some((n, ns))
+}
+
+pub fn parse(s: &str) -> Option<(Temporal, i64)> {
+    if let Some(i) = s.find('D') {
+        let (l, r) = (&s[..i], &s[i + 1..]);
+        let ns = match r {
+            "" => 0,
+            _ => {
+                match clock(r) {
+                    Some((_, x)) => x,
+                    None => return None,
+                }
+            }
+        };
+        if l.contains('.') {
+            return date(l).map(|d| (Temporal::Timestamp, d * NS_DAY + ns));
+        }
+        return match l.parse::<i64>() {
+            Ok(d) => Some((Temporal::Timespan, d * NS_DAY + ns)),
+            Err(_) => None,
+        };
+    }
+    if s.contains(':') {
+        return match clock(s) {
+            Some((2, ns)) => Some((Temporal::Minute, ns / NS_MINUTE)),
+            Some((3, ns)) => Some((Temporal::Second, ns / NS_SECOND)),
+            Some((4, ns)) => Some((Temporal::Time, ns / 1000000)),
+            _ => None,
+        };
+    }
+    date(s).map(|d| (Temporal::Date, d))
+}
+
+fn write_date(days: i64) -> String {
+    let (y, m, d) = civil_from_days(days);
+    format!("{:04}.{:02}.{:02}", y, m, d)
+}
+
+fn write_clock(ns: i64, fields: usize) -> String {
+    let mut s = format!("{:02}:{:02}", ns / NS_HOUR, (ns / NS_MINUTE) % 60);
+    if fields > 2 {
+        s = s + &format!(":{:02}", (ns / NS_SECOND) % 60);
+    }
+    match fields {
+        4 => s + &format!(".{:03}", (ns / 1000000) % 1000),
+        5 => s + &format!(".{:09}", ns % NS_SECOND),
+        _ => s,
+    }
+}
+
+pub fn format(kind: Temporal, value: i64) -> String {
+    if value == ::std::i64::MIN {
+        return "0N".to_string();
+    }
+    match kind {
+        Temporal::Date => write_date(value),
+        Temporal::Minute => write_clock(value * NS_MINUTE, 2),
+        Temporal::Second => write_clock(value * NS_SECOND, 3),
+        Temporal::Time => write_clock(value * 1000000, 4),
+        Temporal::Timestamp => {
+            format!("{}D{}",
+                    write_date(div(value, NS_DAY)),
+                    write_clock(rem(value, NS_DAY), 5))
+        }
+        Temporal::Timespan => {
+            let sign = if value < 0 { "-" } else { "" };
+            let v = value.abs();
+            format!("{}{}D{}", sign, v / NS_DAY, write_clock(v % NS_DAY, 5))
+        }
+    }
+}
+
+// A null operand gives the null of the type the result would have.
+fn nulls<F>(l: (Temporal, i64), r: (Temporal, i64), f: F) -> Option<AST>
+    where F: Fn((Temporal, i64), (Temporal, i64)) -> Option<AST>
+{
+    if l.1 != ast::NULL && r.1 != ast::NULL {
+        return f(l, r);
+    }
+    match f((l.0, 0), (r.0, 0)) {
+        Some(AST::Temporal { kind: k, .. }) => {
+            Some(AST::Temporal {
+                kind: k,
+                value: ast::NULL,
+            })
+        }
+        Some(_) => Some(AST::Int { value: ast::NULL }),
+        None => None,
+    }
+}
+
+pub fn add(l: (Temporal, i64), r: (Temporal, i64)) -> Option<AST> {
+    nulls(l, r, plus)
+}
+
+pub fn sub(l: (Temporal, i64), r: (Temporal, i64)) -> Option<AST> {
+    nulls(l, r, minus)
+}
+
+fn plus(l: (Temporal, i64), r: (Temporal, i64)) -> Option<AST> {
+    match (l, r) {
+        ((Temporal::Timespan, a), (Temporal::Timespan, b)) => {
+            Some(AST::Temporal {
+                kind: Temporal::Timespan,
+                value: a + b,
+            })
+        }
+        ((k, a), (Temporal::Timespan, b)) |
+        ((Temporal::Timespan, b), (k, a)) => {
+            match k {
+                Temporal::Date | Temporal::Timestamp => {
+                    Some(AST::Temporal {
+                        kind: Temporal::Timestamp,
+                        value: a * k.unit() + b,
+                    })
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn minus(l: (Temporal, i64), r: (Temporal, i64)) -> Option<AST> {
+    match (l, r) {
+        ((Temporal::Timestamp, a), (Temporal::Timespan, b)) => {
+            Some(AST::Temporal {
+                kind: Temporal::Timestamp,
+                value: a - b,
+            })
+        }
+        ((Temporal::Timestamp, a), (Temporal::Timestamp, b)) |
+        ((Temporal::Timespan, a), (Temporal::Timespan, b)) => {
+            Some(AST::Temporal {
+                kind: Temporal::Timespan,
+                value: a - b,
+            })
+        }
+        ((k, a), (u, b)) if k == u => Some(AST::Int { value: a - b }),
+        _ => None,
+    }
+}
+
+pub fn component(kind: Temporal, value: i64, name: &str) -> Option<AST> {
+    let ns = value * kind.unit();
+    let (days, clock) = match kind {
+        Temporal::Date | Temporal::Timestamp => (Some(div(ns, NS_DAY)), rem(ns, NS_DAY)),
+        Temporal::Timespan => (None, rem(ns, NS_DAY)),
+        _ => (None, ns),
+    };
+    let civil = days.map(civil_from_days);
+    let int = |v: i64| Some(AST::Int { value: v });
+    let temporal = |k: Temporal, v: i64| {
+        Some(AST::Temporal {
+            kind: k,
+            value: v,
+        })
+    };
+    match (name, civil) {
+        ("year", Some((y, _, _))) => int(y),
+        ("mm", Some((_, m, _))) => int(m),
+        ("dd", Some((_, _, d))) => int(d),
+        ("date", Some(_)) => temporal(Temporal::Date, days.unwrap()),
+        ("hh", _) if kind != Temporal::Date => int(clock / NS_HOUR),
+        ("uu", _) if kind != Temporal::Date => int((clock / NS_MINUTE) % 60),
+        ("ss", _) if kind != Temporal::Date && kind != Temporal::Minute => {
+            int((clock / NS_SECOND) % 60)
+        }
+        ("minute", _) if kind != Temporal::Date => temporal(Temporal::Minute, clock / NS_MINUTE),
+        ("second", _) if kind != Temporal::Date && kind != Temporal::Minute => {
+            temporal(Temporal::Second, clock / NS_SECOND)
+        }
+        ("time", _) if kind != Temporal::Date && kind != Temporal::Minute => {
+            temporal(Temporal::Time, clock / 1000000)
+        }
+        _ => None,
+    }
+}
@@ -26,11 +26,13 @@ lazy_static! {
                                          r"^\]",                                   // 18- CLOSE_B 
                                          r"^\)",                                   // 19- CLOSE_P 
                                          r"^}",                                    // 20- CLOSE_C
-                                         r"^\\\\",]                                // 21- QUIT 
+                                         r"^\\\\",                                 // 21- QUIT 
+                                         r"^(\d{4}\.\d{2}\.\d{2}(D(\d{2}:\d{2}(:\d{2}(\.\d+)?)?)?)?|\d+D\d{2}:\d{2}(:\d{2}(\.\d+)?)?|\d{2}:\d{2}(:\d{2}(\.\d+)?)?)", // 22- TEMPORAL
+                                         r"^/[^\n]*",]                             // 23- COMMENT
                                          .iter().map(|x| Regex::new(x).unwrap()).collect();
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u8)]
 pub enum Token {
     Bool,
@@ -55,6 +57,8 @@ pub enum Token {
     CloseP,
     CloseC,
     Quit,
+    Temporal,
+    Comment,
     Nil,
 }
 
@@ -102,4 +106,99 @@ impl Raw {
     pub fn value(&self) -> &str {
         &self.0
     }
-}
\ No newline at end of file
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    // Zero based line and column of the span start.
+    pub fn position(&self, s: &str) -> (usize, usize) {
+        let before = &s[..self.start];
+        let line = before.matches('\n').count();
+        let col = match before.rfind('\n') {
+            Some(i) => before[i + 1..].chars().count(),
+            None => before.chars().count(),
+        };
+        (line, col)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Lexeme {
+    pub token: Token,
+    pub span: Span,
+}
+
+impl Lexeme {
+    pub fn text<'a>(&self, s: &'a str) -> &'a str {
+        &s[self.span.start..self.span.end]
+    }
+}
+
+// Tokens in the order the parser tries them.
+const LEXICON: [Token; 22] = [Token::Quit,
+                              Token::Temporal,
+                              Token::Ioverb,
+                              Token::Bool,
+                              Token::Hexlit,
+                              Token::Cond,
+                              Token::Number,
+                              Token::String,
+                              Token::Symbol,
+                              Token::Name,
+                              Token::Assign,
+                              Token::Verb,
+                              Token::Adverb,
+                              Token::View,
+                              Token::Colon,
+                              Token::Semi,
+                              Token::OpenB,
+                              Token::OpenP,
+                              Token::OpenC,
+                              Token::CloseB,
+                              Token::CloseP,
+                              Token::CloseC];
+
+// Splits raw source into classified tokens with byte spans. Unlike the parser
+// it keeps comments and never fails: unknown characters become Token::Nil.
+pub fn tokenize(s: &str) -> Vec<Lexeme> {
+    let mut r: Vec<Lexeme> = Vec::new();
+    let mut i = 0;
+    while i < s.len() {
+        let rest = &s[i..];
+        let c = rest.chars().nth(0).unwrap();
+        if c.is_whitespace() {
+            i += c.len_utf8();
+            continue;
+        }
+        let prev = s[..i].chars().rev().nth(0);
+        let (token, len) = if c == '/' && prev.map_or(true, |p| p.is_whitespace()) {
+            (Token::Comment, Token::Comment.find(rest).unwrap().len())
+        } else {
+            // same minus disambiguation as the parser: x-1 is a verb, x -1 a number
+            let minus = c == '-' &&
+                        prev.map_or(false, |p| p.is_alphanumeric() || p == ']' || p == ')');
+            LEXICON.iter()
+                .filter(|t| !(minus && **t == Token::Number))
+                .filter_map(|t| t.find(rest).map(|x| (*t, x.len())))
+                .nth(0)
+                .unwrap_or((Token::Nil, c.len_utf8()))
+        };
+        r.push(Lexeme {
+            token: token,
+            span: Span {
+                start: i,
+                end: i + len,
+            },
+        });
+        i += len;
+    }
+    r
+}